@@ -0,0 +1,32 @@
+use chip8::dispatch::Dispatch;
+use chip8::Chip8;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// Counts in V0 and V1, draws a digit and loops forever.
+const ROM: [u8; 18] = [
+    0x70, 0x01, // ADD V0, 1
+    0x81, 0x04, // ADD V1, V0
+    0x82, 0x13, // XOR V2, V1
+    0xf0, 0x29, // LD F, V0
+    0xd1, 0x25, // DRW V1, V2, 5
+    0x30, 0x00, // SE V0, 0
+    0x12, 0x00, // JP 0x200
+    0x00, 0xe0, // CLS
+    0x12, 0x00, // JP 0x200
+];
+
+fn bench_dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    for (name, dispatch) in [("match", Dispatch::Match), ("table", Dispatch::Table)] {
+        group.bench_function(name, |b| {
+            let mut chip = Chip8::with_seed(0);
+            chip.set_dispatch(dispatch);
+            chip.load_rom(&ROM).expect("error loading rom");
+            b.iter(|| black_box(chip.run(1000, None).expect("emulation error")));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_dispatch);
+criterion_main!(benches);
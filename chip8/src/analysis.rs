@@ -0,0 +1,260 @@
+//! Static analysis of roms.
+//!
+//! Roms mix code and data, so everything here is a best-effort guess,
+//! made either by treating every aligned pair of bytes as an opcode,
+//! or by following the control flow from the start of the rom.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::constants::{PROGRAM_OFFSET, STACK_DEPTH};
+use crate::decode::{decode, Instruction};
+use crate::platform::Platform;
+
+/// How many instructions after a draw are checked for a timer read.
+const DISPLAY_WAIT_WINDOW: usize = 4;
+
+/// Returns the opcodes of the rom, reading every aligned pair of bytes.
+fn opcodes(rom: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    rom.chunks_exact(2)
+        .map(|pair| (pair[0] as u16) << 8 | pair[1] as u16)
+}
+
+/// Returns the opcode at the given offset of the rom, if there is one.
+fn opcode_at(rom: &[u8], offset: usize) -> Option<u16> {
+    rom.get(offset..offset + 2)
+        .map(|pair| (pair[0] as u16) << 8 | pair[1] as u16)
+}
+
+/// Returns the rom offset of an address, if it falls in the rom.
+fn offset_of(rom: &[u8], addr: u16) -> Option<usize> {
+    let offset = (addr as usize).checked_sub(PROGRAM_OFFSET)?;
+    if offset < rom.len() {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
+/// Returns the offsets of the instructions reachable from the start of the rom.
+///
+/// Both branches of skips are followed, as are jumps and calls with a
+/// fixed target. Computed jumps (`BNNN`) and undecodable opcodes end a path.
+fn reachable_code(rom: &[u8]) -> BTreeSet<usize> {
+    walk(rom, 0, true).0
+}
+
+/// Follows the control flow from `start`, returning the offsets of the
+/// instructions reached and the targets of the calls met on the way.
+///
+/// With `into_calls` false, the code of the called subroutines is not
+/// followed, so only the routine starting at `start` is walked.
+fn walk(rom: &[u8], start: usize, into_calls: bool) -> (BTreeSet<usize>, BTreeSet<u16>) {
+    let mut seen = BTreeSet::new();
+    let mut calls = BTreeSet::new();
+    let mut todo = vec![start];
+
+    while let Some(offset) = todo.pop() {
+        let op = match opcode_at(rom, offset) {
+            Some(op) => op,
+            None => continue,
+        };
+        if !seen.insert(offset) {
+            continue;
+        }
+
+        let next = offset + 2;
+        match decode(op) {
+            Some(Instruction::Jp(addr)) => todo.extend(offset_of(rom, addr)),
+            Some(Instruction::Call(addr)) => {
+                todo.push(next);
+                calls.insert(addr);
+                if into_calls {
+                    todo.extend(offset_of(rom, addr));
+                }
+            }
+            Some(Instruction::SeByte(..))
+            | Some(Instruction::SneByte(..))
+            | Some(Instruction::SeReg(..))
+            | Some(Instruction::SneReg(..))
+            | Some(Instruction::Skp(_))
+            | Some(Instruction::Sknp(_)) => {
+                todo.push(next);
+                todo.push(next + 2);
+            }
+            Some(Instruction::Ret)
+            | Some(Instruction::JpV0(_))
+            | Some(Instruction::Exit)
+            | None => {}
+            Some(_) => todo.push(next),
+        }
+    }
+
+    (seen, calls)
+}
+
+/// Estimates the deepest nesting of calls reachable from the start of the rom.
+///
+/// Returns `None` if a subroutine may call itself, directly or not,
+/// as the depth then depends on the state of the machine.
+/// Calls to addresses outside the rom are counted, but not followed.
+pub fn max_call_depth(rom: &[u8]) -> Option<usize> {
+    routine_depth(rom, 0, &mut BTreeMap::new())
+}
+
+/// Returns true if the rom may nest more calls than the stack can hold.
+pub fn may_overflow_stack(rom: &[u8]) -> bool {
+    max_call_depth(rom).is_none_or(|depth| depth > STACK_DEPTH)
+}
+
+/// Returns the call depth of the routine at `offset`.
+///
+/// `depths` holds the routines already measured, and `None` for the ones
+/// being measured, so a call back to them is found as recursion.
+fn routine_depth(
+    rom: &[u8],
+    offset: usize,
+    depths: &mut BTreeMap<usize, Option<usize>>,
+) -> Option<usize> {
+    if let Some(&depth) = depths.get(&offset) {
+        return depth;
+    }
+    depths.insert(offset, None);
+
+    let mut depth = 0;
+    for addr in walk(rom, offset, false).1 {
+        let callee = match offset_of(rom, addr) {
+            Some(callee) => routine_depth(rom, callee, depths)?,
+            None => 0,
+        };
+        depth = depth.max(callee + 1);
+    }
+
+    depths.insert(offset, Some(depth));
+    Some(depth)
+}
+
+/// Returns the opcodes the rom seems to use, but that the emulator
+/// can't run on the given platform, in order of appearance.
+///
+/// Only the code reachable from the start of the rom is checked.
+pub fn unsupported_opcodes(rom: &[u8], platform: Platform) -> Vec<u16> {
+    reachable_code(rom)
+        .into_iter()
+        .filter_map(|offset| opcode_at(rom, offset))
+        .filter(|&op| !decode(op).is_some_and(|ins| platform.supports(ins)))
+        .collect()
+}
+
+/// Counts the instructions of the rom by mnemonic, like `LD` or `DRW`,
+/// reading every aligned pair of bytes.
+///
+/// Opcodes that are not valid instructions, usually data, count as `DB`.
+pub fn opcode_histogram(rom: &[u8]) -> BTreeMap<&'static str, usize> {
+    let mut histogram = BTreeMap::new();
+    for op in opcodes(rom) {
+        let class = decode(op).map_or("DB", Instruction::mnemonic);
+        *histogram.entry(class).or_insert(0) += 1;
+    }
+    histogram
+}
+
+/// Returns true if the instruction never falls through to the next one.
+fn is_jump(ins: &Option<Instruction>) -> bool {
+    matches!(
+        ins,
+        Some(Instruction::Jp(_) | Instruction::JpV0(_) | Instruction::Ret | Instruction::Exit)
+    )
+}
+
+/// Guesses if the rom expects draws to wait for the display.
+///
+/// Roms written for the original interpreter often read the delay timer
+/// right after drawing, to sync with the screen refresh.
+/// This looks for a timer read in the few instructions following a draw.
+pub fn suggest_display_wait(rom: &[u8]) -> bool {
+    let ops: Vec<Option<Instruction>> = opcodes(rom).map(decode).collect();
+
+    ops.iter().enumerate().any(|(n, ins)| {
+        matches!(ins, Some(Instruction::Drw(..)))
+            && ops[n + 1..]
+                .iter()
+                .take(DISPLAY_WAIT_WINDOW)
+                .take_while(|ins| !is_jump(ins))
+                .any(|ins| matches!(ins, Some(Instruction::LdVxDt(_))))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_in_base_mode() {
+        // HIGH; CLS; JP 0x208; (data) 0xFFFF; LD V0, 1; 0xF0FF; RET
+        let rom = [
+            0x00, 0xff, 0x00, 0xe0, 0x12, 0x08, 0xff, 0xff, 0x60, 0x01, 0xf0, 0xff, 0x00, 0xee,
+        ];
+        assert_eq!(
+            unsupported_opcodes(&rom, Platform::Chip8),
+            vec![0x00ff, 0xf0ff]
+        );
+        assert_eq!(unsupported_opcodes(&rom, Platform::SuperChip), vec![0xf0ff]);
+    }
+
+    #[test]
+    fn nested_calls() {
+        // CALL 0x206; CALL 0x20a; JP 0x204;
+        // 0x206: CALL 0x20a; RET;
+        // 0x20a: SE V0, 0; CALL 0x210; RET;
+        // 0x210: RET
+        let rom = [
+            0x22, 0x06, 0x22, 0x0a, 0x12, 0x04, 0x22, 0x0a, 0x00, 0xee, 0x30, 0x00, 0x22, 0x10,
+            0x00, 0xee, 0x00, 0xee,
+        ];
+        assert_eq!(max_call_depth(&rom), Some(3));
+        assert!(!may_overflow_stack(&rom));
+
+        // CALL 0x204; JP 0x202; 0x204: CALL 0x204; RET
+        let rom = [0x22, 0x04, 0x12, 0x02, 0x22, 0x04, 0x00, 0xee];
+        assert_eq!(max_call_depth(&rom), None);
+        assert!(may_overflow_stack(&rom));
+
+        // 17 nested calls
+        let mut rom = Vec::new();
+        for n in 1..=17u16 {
+            rom.extend((0x2200 + n * 4).to_be_bytes());
+            rom.extend([0x00, 0xee]);
+        }
+        rom.extend([0x00, 0xee]);
+        assert_eq!(max_call_depth(&rom), Some(17));
+        assert!(may_overflow_stack(&rom));
+    }
+
+    #[test]
+    fn histogram() {
+        // LD V0, 1; LD I, 0x300; ADD V0, 1; DRW V0, V1, 5; JP 0x204; (data) 0xFFFF
+        let rom = [
+            0x60, 0x01, 0xa3, 0x00, 0x70, 0x01, 0xd0, 0x15, 0x12, 0x04, 0xff, 0xff,
+        ];
+        let histogram = opcode_histogram(&rom);
+        assert_eq!(histogram.len(), 5);
+        assert_eq!(histogram["LD"], 2);
+        assert_eq!(histogram["ADD"], 1);
+        assert_eq!(histogram["DRW"], 1);
+        assert_eq!(histogram["JP"], 1);
+        assert_eq!(histogram["DB"], 1);
+    }
+
+    #[test]
+    fn draw_then_wait() {
+        // DRW V0, V1, 5; LD DT, V2; LD V3, DT; SE V3, 0; JP 0x204
+        let rom = [0xd0, 0x15, 0xf2, 0x15, 0xf3, 0x07, 0x33, 0x00, 0x12, 0x04];
+        assert!(suggest_display_wait(&rom));
+
+        // DRW V0, V1, 5; ADD V0, 1; JP 0x200
+        let rom = [0xd0, 0x15, 0x70, 0x01, 0x12, 0x00, 0xf3, 0x07];
+        assert!(!suggest_display_wait(&rom));
+    }
+}
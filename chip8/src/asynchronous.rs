@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+use crate::error::ChipError;
+use crate::Chip8;
+
+/// Runs a `Chip8` from an async task, one frame per tick of a timer.
+///
+/// The emulation itself stays synchronous: each call to `run_frame`
+/// waits for the next tick, then runs a whole frame.
+#[derive(Debug)]
+pub struct AsyncChip8 {
+    chip: Chip8,
+    ipf: usize,
+    period: Duration,
+    // created on the first frame, as it needs a running runtime
+    timer: Option<Interval>,
+}
+
+impl AsyncChip8 {
+    /// Wraps the machine, running `ipf` instructions per frame
+    /// at the timer rate of the machine.
+    pub fn new(chip: Chip8, ipf: usize) -> Self {
+        let period = chip.frame_period();
+        Self::with_period(chip, ipf, period)
+    }
+
+    /// Wraps the machine, running a frame every `period`.
+    pub fn with_period(chip: Chip8, ipf: usize, period: Duration) -> Self {
+        AsyncChip8 {
+            chip,
+            ipf,
+            period,
+            timer: None,
+        }
+    }
+
+    /// Waits for the next frame, then runs it.
+    /// Returns how many instructions ran.
+    pub async fn run_frame(&mut self) -> Result<usize, ChipError> {
+        let period = self.period;
+        let timer = self.timer.get_or_insert_with(|| {
+            let mut timer = interval(period);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            timer
+        });
+        timer.tick().await;
+        self.chip.frame(self.ipf)
+    }
+
+    /// Returns the machine.
+    pub fn chip(&self) -> &Chip8 {
+        &self.chip
+    }
+
+    /// Returns the machine, to press keys or load roms.
+    pub fn chip_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip
+    }
+
+    /// Returns the machine, ending the async wrapper.
+    pub fn into_inner(self) -> Chip8 {
+        self.chip
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn run_frames() {
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0x70, 0x01, 0x12, 0x00])
+            .expect("error loading rom");
+        let mut runner = AsyncChip8::with_period(chip, 2, Duration::from_millis(1));
+
+        runner.run_frame().await.expect("emulation error");
+        assert_eq!(runner.chip().frame_count(), 1);
+        runner.run_frame().await.expect("emulation error");
+        assert_eq!(runner.chip().frame_count(), 2);
+        assert_eq!(runner.into_inner().get_regs()[0], 2);
+    }
+}
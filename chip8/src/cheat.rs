@@ -0,0 +1,106 @@
+use crate::constants::MEM_SIZE;
+use crate::error::DebugChipError;
+use crate::Chip8;
+
+/// Forces a memory cell to a value at the end of every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cheat {
+    pub addr: usize,
+    pub value: u8,
+}
+
+impl Chip8 {
+    /// Adds a cheat, applied after each frame.
+    pub fn add_cheat(&mut self, cheat: Cheat) -> Result<(), DebugChipError> {
+        if cheat.addr >= MEM_SIZE {
+            return Err(DebugChipError::AddrOutOfBounds(cheat.addr));
+        }
+        self.cheats.push(cheat);
+
+        Ok(())
+    }
+
+    /// Removes the cheats on the given address.
+    pub fn remove_cheat(&mut self, addr: usize) {
+        self.cheats.retain(|c| c.addr != addr);
+    }
+
+    /// Removes all the cheats.
+    pub fn clear_cheats(&mut self) {
+        self.cheats.clear();
+    }
+
+    /// Writes the cheat values that are not in memory already,
+    /// so the memory write log only shows the cheats changing a value.
+    pub(crate) fn apply_cheats(&mut self) {
+        for n in 0..self.cheats.len() {
+            let Cheat { addr, value } = self.cheats[n];
+            if self.mem[addr] != value {
+                self.write_mem_from(addr, value, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryWrite;
+
+    #[test]
+    fn frozen_memory() {
+        // LD I, 0x300; LD V0, [I]; ADD V0, 1; LD [I], V0; JP 0x200
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0xa3, 0x00, 0xf0, 0x65, 0x70, 0x01, 0xf0, 0x55, 0x12, 0x00])
+            .expect("error loading rom");
+        chip.add_cheat(Cheat {
+            addr: 0x300,
+            value: 0x63,
+        })
+        .expect("debug error");
+
+        for _ in 0..4 {
+            chip.frame(5).expect("emulation error");
+            assert_eq!(chip.get_mem()[0x300], 0x63);
+        }
+        // the rom read the frozen value
+        assert_eq!(chip.get_regs()[0], 0x64);
+
+        chip.remove_cheat(0x300);
+        chip.frame(5).expect("emulation error");
+        assert_eq!(chip.get_mem()[0x300], 0x64);
+
+        assert!(chip
+            .add_cheat(Cheat {
+                addr: 0x1000,
+                value: 0
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn cheat_writes_logged() {
+        // LD I, 0x300; LD V0, [I]; ADD V0, 1; LD [I], V0; JP 0x200
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0xa3, 0x00, 0xf0, 0x65, 0x70, 0x01, 0xf0, 0x55, 0x12, 0x00])
+            .expect("error loading rom");
+        for (addr, value) in [(0x300, 0x63), (0x400, 0x00)] {
+            chip.add_cheat(Cheat { addr, value }).expect("debug error");
+        }
+        chip.set_memory_write_log(true);
+        chip.frame(5).expect("emulation error");
+
+        // 0x400 already holds its value, so only the write to 0x300 is undone,
+        // by the cheat rather than an instruction
+        let write = |old, new, pc| MemoryWrite {
+            addr: 0x300,
+            old,
+            new,
+            pc,
+        };
+        assert_eq!(
+            chip.take_memory_writes(),
+            [write(0x00, 0x01, Some(0x206)), write(0x01, 0x63, None)]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! The clock checked by the runs with a deadline.
+
+#[cfg(feature = "std")]
+pub use std::time::Instant;
+
+/// Without `std` there is no clock, so no deadline can be given,
+/// and the runs with a deadline only take `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg(not(feature = "std"))]
+pub enum Instant {}
+
+/// Returns true if the deadline passed.
+#[cfg(feature = "std")]
+pub(crate) fn passed(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|d| Instant::now() >= d)
+}
+
+/// Returns true if the deadline passed.
+#[cfg(not(feature = "std"))]
+pub(crate) fn passed(deadline: Option<Instant>) -> bool {
+    match deadline {
+        Some(never) => match never {},
+        None => false,
+    }
+}
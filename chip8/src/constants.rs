@@ -1,6 +1,22 @@
 pub const MEM_SIZE: usize = 0x1000;
+pub const PROGRAM_OFFSET: usize = 0x200;
+/// The biggest rom that fits in memory.
+pub const MAX_ROM_SIZE: usize = MEM_SIZE - PROGRAM_OFFSET;
+/// The flag register, written by arithmetic, shift and draw instructions.
+pub const VF: usize = 0xf;
+/// The rate of the timers on NTSC machines, in Hz.
+pub const DEFAULT_TIMER_HZ: u32 = 60;
+/// How many calls can be nested before the stack overflows.
+pub const STACK_DEPTH: usize = 16;
+/// How many states `step_back` can go back by default.
+pub const DEFAULT_REWIND_DEPTH: usize = 256;
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+pub const SCRATCH_OFFSET: usize = 0x100;
+pub const SCRATCH_SIZE: usize = 0x100;
 
 pub const FONT_OFFSET: usize = 0x50;
 pub const FONT_SPRITES: [u8; 0x50] = [
@@ -1,6 +1,55 @@
-use crate::constants::{MEM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
-use crate::error::DebugChipError;
-use crate::Chip8;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+use crate::constants::{MEM_SIZE, SCRATCH_OFFSET, SCRATCH_SIZE, STACK_DEPTH, VF};
+use crate::decode::{decode, Instruction};
+use crate::disasm::disassemble;
+use crate::error::{ChipError, DebugChipError};
+use crate::{Chip8, MemoryWrite};
+
+/// An executed instruction, with the state of the machine before it ran.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HistoryEntry {
+    pub(crate) pc: u16,
+    pub(crate) op: u16,
+    pub(crate) v: [u8; 0x10],
+    pub(crate) i: u16,
+    pub(crate) sp: u8,
+    pub(crate) dt: u8,
+    pub(crate) st: u8,
+}
+
+/// The state of the machine when an instruction failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashReport {
+    /// Address of the failed instruction.
+    pub pc: u16,
+    /// The failed opcode, or `None` if `pc` was out of memory.
+    pub opcode: Option<u16>,
+    pub regs: [u8; 0x10],
+    pub i: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    /// Address and opcode of the last instructions, oldest first,
+    /// as kept by `set_history_depth`.
+    pub history: Vec<(u16, u16)>,
+    /// The error returned by the instruction.
+    pub error: String,
+}
+
+/// What an instruction run by `step_traced` did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepInfo {
+    /// Address of the instruction.
+    pub pc: u16,
+    pub opcode: u16,
+    /// The mnemonic of the instruction, like `LD` or `DRW`.
+    pub mnemonic: &'static str,
+    /// The registers the instruction wrote, in order,
+    /// even if it wrote the value they already had.
+    pub written_regs: Vec<usize>,
+}
 
 /// The debug functions.
 impl Chip8 {
@@ -16,7 +65,7 @@ impl Chip8 {
 
     /// Returns the program counter, the stack pointer and the index register
     /// in this order.
-    pub fn get_pointers(&self) -> (u16, usize, u16) {
+    pub fn get_pointers(&self) -> (u16, u8, u16) {
         (self.pc, self.sp, self.i)
     }
 
@@ -30,11 +79,195 @@ impl Chip8 {
         self.stack
     }
 
+    /// Returns the return addresses of the active calls, outermost first.
+    ///
+    /// `sp` counts the active entries, which are `stack[..sp]`.
+    pub fn call_stack(&self) -> Vec<u16> {
+        self.stack[..self.sp as usize].to_vec()
+    }
+
     /// Returns the keypad status.
     pub fn get_keypad(&self) -> [bool; 16] {
         self.keypad
     }
 
+    /// Sets how many executed instructions are kept in the history.
+    /// A depth of 0 disables the history.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.history_depth = depth;
+        while self.history.len() > depth {
+            self.history.pop_front();
+        }
+    }
+
+    /// Returns the address and opcode of the last executed instructions,
+    /// oldest first.
+    pub fn history(&self) -> Vec<(u16, u16)> {
+        self.history.iter().map(|e| (e.pc, e.op)).collect()
+    }
+
+    /// Starts or stops saving the state before each instruction,
+    /// for `step_back` to return to. Stopping drops the saved states.
+    ///
+    /// Each state holds all of memory, so this is off by default.
+    pub fn record_rewind(&mut self, record: bool) {
+        self.record_rewind = record;
+        if !record {
+            self.rewind.clear();
+        }
+    }
+
+    /// Sets how many states `step_back` can go back,
+    /// `DEFAULT_REWIND_DEPTH` unless changed.
+    pub fn set_rewind_depth(&mut self, depth: usize) {
+        self.rewind_depth = depth;
+        while self.rewind.len() > depth {
+            self.rewind.pop_front();
+        }
+    }
+
+    /// Puts the machine back in the state before the last executed instruction,
+    /// if `record_rewind` was saving it.
+    ///
+    /// The instruction and frame counts are not rewound,
+    /// but the random number generator is, so `CXNN` draws the same numbers again.
+    pub fn step_back(&mut self) -> Result<(), ChipError> {
+        let state = self.rewind.pop_back().ok_or(ChipError::NoRewind)?;
+        self.restore(&state);
+        Ok(())
+    }
+
+    /// Returns the history as text, one instruction per line,
+    /// like `0x200: 6105  LD V1, 0x05`.
+    pub fn dump_history(&self) -> String {
+        self.history
+            .iter()
+            .map(|e| format!("{:#05X}: {:04X}  {}\n", e.pc, e.op, disassemble(e.op)))
+            .collect()
+    }
+
+    /// Returns the history as a trace to diff against other emulators,
+    /// one instruction per line, with the state before it ran:
+    ///
+    /// `PC:0200 OP:6105 I:0000 SP:0 DT:00 ST:00 V:00000000000000000000000000000000`
+    ///
+    /// Numbers are uppercase hex, and `V` lists the registers from `V0` to `VF`.
+    pub fn dump_trace(&self) -> String {
+        self.history
+            .iter()
+            .map(|e| {
+                let v: String = e.v.iter().map(|r| format!("{:02X}", r)).collect();
+                format!(
+                    "PC:{:04X} OP:{:04X} I:{:04X} SP:{:X} DT:{:02X} ST:{:02X} V:{}\n",
+                    e.pc, e.op, e.i, e.sp, e.dt, e.st, v
+                )
+            })
+            .collect()
+    }
+
+    /// Executes the next instruction, returning its address and disassembly.
+    pub fn step_disasm(&mut self) -> Result<(u16, String), ChipError> {
+        let addr = self.pc;
+        let op = self.step()?;
+        Ok((addr, disassemble(op)))
+    }
+
+    /// Executes the next instruction, returning what it did.
+    pub fn step_traced(&mut self) -> Result<StepInfo, ChipError> {
+        let pc = self.pc;
+        let opcode = self.step()?;
+        let ins = decode(opcode);
+        Ok(StepInfo {
+            pc,
+            opcode,
+            mnemonic: ins.map_or("DB", Instruction::mnemonic),
+            written_regs: ins.map_or(vec![], |ins| self.written_regs(ins, pc)),
+        })
+    }
+
+    /// Returns the registers written by an instruction that just ran from `pc`.
+    fn written_regs(&self, ins: Instruction, pc: u16) -> Vec<usize> {
+        use Instruction::*;
+        let mut regs = match ins {
+            LdByte(x, _) | AddByte(x, _) | LdReg(x, _) | Rnd(x, _) | LdVxDt(x) => vec![x],
+            Or(x, _) | And(x, _) | Xor(x, _) if self.quirks.logic_resets_vf => vec![x, VF],
+            Or(x, _) | And(x, _) | Xor(x, _) => vec![x],
+            AddReg(x, _) | Sub(x, _) | Shr(x, _) | Subn(x, _) | Shl(x, _) => vec![x, VF],
+            Drw(..) => vec![VF],
+            // still waiting if pc stayed on the instruction
+            LdVxK(x) if self.pc != pc => vec![x],
+            LdVxI(x) => (0..=x).collect(),
+            _ => vec![],
+        };
+        regs.sort_unstable();
+        regs.dedup();
+        regs
+    }
+
+    /// Returns a short summary of the machine state, for logging.
+    ///
+    /// The first line has the registers, like `V0=1F V1=00 ...`,
+    /// the second line has the pointers and timers, and the last one
+    /// shows each pressed key as its hex digit and the others as `.`.
+    pub fn summary(&self) -> String {
+        let regs: Vec<String> = self
+            .v
+            .iter()
+            .enumerate()
+            .map(|(r, val)| format!("V{:X}={:02X}", r, val))
+            .collect();
+        let keys: String = self
+            .keypad
+            .iter()
+            .enumerate()
+            .map(|(k, &p)| {
+                if p {
+                    format!("{:X}", k)
+                } else {
+                    ".".to_string()
+                }
+            })
+            .collect();
+        format!(
+            "{}\nPC={:#05X} I={:#05X} SP={} DT={} ST={}\nKEYS={}",
+            regs.join(" "),
+            self.pc,
+            self.i,
+            self.sp,
+            self.dt,
+            self.st,
+            keys
+        )
+    }
+
+    /// Enables or disables the log of the memory writes made by instructions
+    /// and cheats.
+    /// Disabling it drops the writes not taken yet.
+    pub fn set_memory_write_log(&mut self, enabled: bool) {
+        self.memory_writes = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    /// Returns the memory writes logged since the last call, oldest first.
+    pub fn take_memory_writes(&mut self) -> Vec<MemoryWrite> {
+        self.memory_writes
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Returns every address where the given bytes appear in memory.
+    pub fn search_memory(&self, pattern: &[u8]) -> Vec<usize> {
+        if pattern.is_empty() {
+            return vec![];
+        }
+        self.mem
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, w)| *w == pattern)
+            .map(|(addr, _)| addr)
+            .collect()
+    }
+
     /// Writes a value at the given memory address.
     pub fn set_mem(&mut self, addr: usize, val: u8) -> Result<(), DebugChipError> {
         if addr > 0xfff {
@@ -45,20 +278,174 @@ impl Chip8 {
         Ok(())
     }
 
+    /// Reads a pixel of the frame buffer.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Result<bool, DebugChipError> {
+        let (width, height) = self.dimensions();
+        if x >= width || y >= height {
+            return Err(DebugChipError::NoPixel(x, y));
+        }
+
+        Ok(self.fb[self.pixel_index(x, y)])
+    }
+
     /// Writes a pixel on the frame buffer.
     /// Does not compute collision.
     pub fn set_fb(&mut self, x: usize, y: usize, pixel: bool) -> Result<(), DebugChipError> {
-        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+        let (width, height) = self.dimensions();
+        if x >= width || y >= height {
+            return Err(DebugChipError::NoPixel(x, y));
+        }
+        let p = self.pixel_index(x, y);
+        self.fb[p] = pixel;
+        self.fb_dirty = true;
+
+        Ok(())
+    }
+
+    /// Enables collecting a `CrashReport` when an instruction fails.
+    pub fn set_crash_reports(&mut self, enabled: bool) {
+        self.crash_reports = enabled;
+    }
+
+    /// Returns the report of the last failed instruction, kept across resets.
+    pub fn crash_report(&self) -> Option<&CrashReport> {
+        self.crash_report.as_ref()
+    }
+
+    /// Makes `run_until_breakpoint` stop before the instruction at `addr`.
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// Removes the breakpoint at `addr`, if any.
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// Removes all the breakpoints.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Executes up to `max_steps` instructions, without frames or timers,
+    /// stopping before an instruction with a breakpoint.
+    ///
+    /// Returns the address of the breakpoint, or `None` if all the steps ran.
+    /// A run resuming from the breakpoint it stopped at runs its instruction,
+    /// until any instruction has run since the stop.
+    pub fn run_until_breakpoint(&mut self, max_steps: usize) -> Result<Option<u16>, ChipError> {
+        for _ in 0..max_steps {
+            if self.breakpoints.contains(&self.pc) && self.stopped_at != Some(self.pc) {
+                self.stopped_at = Some(self.pc);
+                return Ok(Some(self.pc));
+            }
+            self.step()?;
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn make_crash_report(&self, error: &ChipError) -> CrashReport {
+        let pc = self.pc as usize;
+        CrashReport {
+            pc: self.pc,
+            opcode: self
+                .mem
+                .get(pc..pc + 2)
+                .map(|op| (op[0] as u16) << 8 | op[1] as u16),
+            regs: self.v,
+            i: self.i,
+            sp: self.sp,
+            stack: self.stack,
+            history: self.history(),
+            error: error.to_string(),
+        }
+    }
+
+    /// Collects a warning the first time each SUPER-CHIP instruction
+    /// not supported on the platform set with `set_platform` runs.
+    /// The instruction still runs, or fails, as it does without warnings.
+    /// See `take_warnings`.
+    pub fn set_schip_warnings(&mut self, enabled: bool) {
+        self.schip_warnings = enabled;
+    }
+
+    /// Returns the warnings collected since the last call.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.warnings)
+    }
+
+    /// Turns off the pixels of a `w` by `h` rectangle, with its top left corner
+    /// at (`x`, `y`). The whole rectangle must be on the screen.
+    pub fn clear_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+    ) -> Result<(), DebugChipError> {
+        let (width, height) = self.dimensions();
+        let (right, bottom) = (x.saturating_add(w), y.saturating_add(h));
+        if right > width || bottom > height {
+            // the far corner, or the origin of an empty rectangle
+            return Err(DebugChipError::NoPixel(
+                right.saturating_sub(1).max(x),
+                bottom.saturating_sub(1).max(y),
+            ));
+        }
+        for row in y..bottom {
+            let start = self.pixel_index(x, row);
+            self.fb[start..start + w].fill(false);
+        }
+        self.fb_dirty = true;
+
+        Ok(())
+    }
+
+    /// Draws a sprite on the frame buffer like `DXYN` does, without using
+    /// memory or registers.
+    /// Returns true if a collision occurred.
+    pub fn debug_draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+    ) -> Result<bool, DebugChipError> {
+        let (width, height) = self.dimensions();
+        if x >= width || y >= height {
             return Err(DebugChipError::NoPixel(x, y));
         }
-        self.fb[y][x] = pixel;
+
+        Ok(self.draw_sprite(x, y, sprite))
+    }
+
+    /// Switches between the 64x32 and the SUPER-CHIP 128x64 resolutions,
+    /// clearing the screen.
+    pub fn set_resolution(&mut self, high: bool) {
+        self.hires = high;
+        self.fb = self.blank_fb();
+        self.fb_dirty = true;
+    }
+
+    /// Returns a copy of the frame buffer, row by row.
+    pub fn save_display(&self) -> Vec<bool> {
+        self.fb.clone()
+    }
+
+    /// Restores a frame buffer saved with `save_display`.
+    pub fn restore_display(&mut self, pixels: &[bool]) -> Result<(), DebugChipError> {
+        let (width, height) = self.dimensions();
+        if pixels.len() != width * height {
+            return Err(DebugChipError::BadDisplaySize(pixels.len()));
+        }
+        self.fb.copy_from_slice(pixels);
+        self.fb_dirty = true;
 
         Ok(())
     }
 
     /// Writes a value in the given register.
     pub fn set_reg(&mut self, reg: usize, val: u8) -> Result<(), DebugChipError> {
-        if reg > 0x10 {
+        if reg >= self.v.len() {
             return Err(DebugChipError::NoRegister(reg));
         }
         self.v[reg] = val;
@@ -97,9 +484,9 @@ impl Chip8 {
     }
 
     /// Sets the stack pointer.
-    pub fn set_sp(&mut self, val: usize) -> Result<(), DebugChipError> {
-        if val > 15 {
-            return Err(DebugChipError::SpOutOfBounds(val));
+    pub fn set_sp(&mut self, val: u8) -> Result<(), DebugChipError> {
+        if val as usize > STACK_DEPTH {
+            return Err(DebugChipError::SpOutOfBounds(val as usize));
         }
 
         self.sp = val;
@@ -118,4 +505,511 @@ impl Chip8 {
         self.stack[pos] = val;
         Ok(())
     }
+
+    /// Injects a sequence of opcodes to run before the current instruction.
+    ///
+    /// The opcodes are written in the scratch region, `0x100` to `0x1FF`,
+    /// followed by a jump back to the current `pc`, and `pc` is moved to
+    /// the scratch region. Whatever the region held is overwritten and lost.
+    ///
+    /// Coming back to the original `pc` is not guaranteed: the trailing jump
+    /// only runs if the injected opcodes reach it, so an opcode that jumps,
+    /// calls, skips past it or fails leaves `pc` somewhere else.
+    pub fn queue_opcodes(&mut self, ops: &[u16]) -> Result<(), DebugChipError> {
+        if (ops.len() + 1) * 2 > SCRATCH_SIZE {
+            return Err(DebugChipError::ScriptTooLong(ops.len()));
+        }
+
+        let ret = 0x1000 | (self.pc & 0x0fff);
+        for (n, op) in ops.iter().chain(core::iter::once(&ret)).enumerate() {
+            let addr = SCRATCH_OFFSET + 2 * n;
+            self.mem[addr..addr + 2].copy_from_slice(&op.to_be_bytes());
+        }
+        self.pc = SCRATCH_OFFSET as u16;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::{FONT_OFFSET, FONT_SPRITES};
+    use crate::error::DebugChipError;
+    use crate::platform::Platform;
+    use crate::{Chip8, MemoryWrite, StepInfo};
+
+    #[test]
+    fn draw_sprite_like_drw() {
+        // LD V0, 3; LD V1, 4; LD F, V1; DRW V0, V1, 5
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0x60, 0x03, 0x61, 0x04, 0xf1, 0x29, 0xd0, 0x15])
+            .expect("error loading rom");
+        for _ in 0..4 {
+            chip.step().expect("emulation error");
+        }
+
+        let mut other = Chip8::new();
+        let glyph = &FONT_SPRITES[4 * 5..5 * 5];
+        assert_eq!(other.debug_draw_sprite(3, 4, glyph).ok(), Some(false));
+        assert_eq!(chip.fb_flat(), other.fb_flat());
+        assert_eq!(chip.get_mem()[FONT_OFFSET + 4 * 5], glyph[0]);
+
+        assert_eq!(other.debug_draw_sprite(3, 4, glyph).ok(), Some(true));
+        assert!(other.debug_draw_sprite(64, 0, glyph).is_err());
+
+        // single pixels mark the screen as changed too
+        let mut chip = Chip8::new();
+        chip.set_fb(3, 4, true).expect("debug error");
+        assert!(chip.fb_dirty);
+    }
+
+    #[test]
+    fn dump_history() {
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0x61, 0x05, 0x22, 0x06, 0x00, 0x00, 0x81, 0x14, 0x00, 0xee])
+            .expect("error loading rom");
+        chip.set_history_depth(3);
+        for _ in 0..4 {
+            chip.step().expect("emulation error");
+        }
+
+        assert_eq!(
+            chip.dump_history(),
+            "0x202: 2206  CALL 0x206\n\
+             0x206: 8114  ADD V1, V1\n\
+             0x208: 00EE  RET\n"
+        );
+    }
+
+    #[test]
+    fn step_back() {
+        // LD V1, 0x05; ADD V1, 0x03; CALL 0x208; 0x0000;
+        // LD F, V0; DRW V0, V1, 5; RET
+        let rom = [
+            0x61, 0x05, 0x71, 0x03, 0x22, 0x08, 0x00, 0x00, 0xf0, 0x29, 0xd0, 0x15, 0x00, 0xee,
+        ];
+        let mut chip = Chip8::new();
+        chip.load_rom(&rom).expect("error loading rom");
+        chip.step().expect("emulation error");
+        // nothing is saved unless asked
+        assert!(chip.step_back().is_err());
+
+        chip.reset();
+        chip.load_rom(&rom).expect("error loading rom");
+        chip.record_rewind(true);
+        let mut states = Vec::new();
+        for _ in 0..6 {
+            states.push(chip.snapshot());
+            chip.step().expect("emulation error");
+        }
+        assert_eq!(chip.get_pointers(), (0x206, 0, 0x50));
+        assert!(chip.fb.iter().any(|&p| p));
+
+        while let Some(state) = states.pop() {
+            chip.step_back().expect("emulation error");
+            assert_eq!(chip.snapshot(), state);
+        }
+        assert!(chip.step_back().is_err());
+
+        // only the last states are kept
+        chip.set_rewind_depth(2);
+        for _ in 0..6 {
+            chip.step().expect("emulation error");
+        }
+        chip.step_back().expect("emulation error");
+        chip.step_back().expect("emulation error");
+        assert!(chip.step_back().is_err());
+        assert_eq!(chip.get_pointers().0, 0x20a);
+    }
+
+    #[test]
+    fn memory_write_log() {
+        let mut chip = Chip8::new();
+        // LD V0, 0x11; LD V1, 0x22; LD I, 0x300; LD [I], V1
+        chip.load_rom(&[0x60, 0x11, 0x61, 0x22, 0xa3, 0x00, 0xf1, 0x55])
+            .expect("error loading rom");
+        chip.set_mem(0x301, 0x99).expect("debug error");
+        chip.set_memory_write_log(true);
+        for _ in 0..4 {
+            chip.step().expect("emulation error");
+        }
+
+        let write = |addr, old, new| MemoryWrite {
+            addr,
+            old,
+            new,
+            pc: Some(0x206),
+        };
+        assert_eq!(
+            chip.take_memory_writes(),
+            [write(0x300, 0x00, 0x11), write(0x301, 0x99, 0x22)]
+        );
+        assert!(chip.take_memory_writes().is_empty());
+    }
+
+    #[test]
+    fn dump_trace() {
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0x61, 0x05, 0x81, 0x14])
+            .expect("error loading rom");
+        chip.set_history_depth(2);
+        chip.set_dt(0x20);
+        chip.step().expect("emulation error");
+        chip.step().expect("emulation error");
+
+        assert_eq!(
+            chip.dump_trace(),
+            "PC:0200 OP:6105 I:0000 SP:0 DT:20 ST:00 V:00000000000000000000000000000000\n\
+             PC:0202 OP:8114 I:0000 SP:0 DT:20 ST:00 V:00050000000000000000000000000000\n"
+        );
+    }
+
+    #[test]
+    fn call_stack() {
+        let mut chip = Chip8::new();
+        // CALL 0x204; (padding); CALL 0x208; (padding); CLS
+        chip.load_rom(&[0x22, 0x04, 0x00, 0x00, 0x22, 0x08, 0x00, 0x00, 0x00, 0xe0])
+            .expect("error loading rom");
+        assert!(chip.call_stack().is_empty());
+
+        chip.step().expect("emulation error");
+        chip.step().expect("emulation error");
+        assert_eq!(chip.call_stack(), [0x200, 0x204]);
+    }
+
+    #[test]
+    fn step_disasm() {
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0x61, 0x05, 0x22, 0x06, 0x00, 0x00, 0x81, 0x14, 0x00, 0xee])
+            .expect("error loading rom");
+
+        let trace: Vec<(u16, String)> = (0..4)
+            .map(|_| chip.step_disasm().expect("emulation error"))
+            .collect();
+        assert_eq!(
+            trace,
+            [
+                (0x200, "LD V1, 0x05".to_string()),
+                (0x202, "CALL 0x206".to_string()),
+                (0x206, "ADD V1, V1".to_string()),
+                (0x208, "RET".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut chip = Chip8::new();
+        // LD V0, 0; ADD V0, 1; JP 0x202
+        chip.load_rom(&[0x60, 0x00, 0x70, 0x01, 0x12, 0x02])
+            .expect("error loading rom");
+        chip.add_breakpoint(0x202);
+
+        // stops before running the instruction
+        assert_eq!(
+            chip.run_until_breakpoint(100).expect("emulation error"),
+            Some(0x202)
+        );
+        assert_eq!(chip.get_regs()[0], 0);
+        assert_eq!(
+            chip.run_until_breakpoint(100).expect("emulation error"),
+            Some(0x202)
+        );
+        assert_eq!(chip.get_regs()[0], 1);
+
+        chip.add_breakpoint(0x204);
+        assert_eq!(
+            chip.run_until_breakpoint(100).expect("emulation error"),
+            Some(0x204)
+        );
+        chip.remove_breakpoint(0x202);
+        assert_eq!(
+            chip.run_until_breakpoint(100).expect("emulation error"),
+            Some(0x204)
+        );
+        assert_eq!(chip.get_regs()[0], 3);
+
+        chip.clear_breakpoints();
+        assert_eq!(
+            chip.run_until_breakpoint(10).expect("emulation error"),
+            None
+        );
+        assert_eq!(chip.get_regs()[0], 8);
+
+        // a breakpoint at the entry point stops before anything runs
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0x60, 0x00, 0x70, 0x01, 0x12, 0x02])
+            .expect("error loading rom");
+        chip.add_breakpoint(0x200);
+        chip.add_breakpoint(0x202);
+        assert_eq!(
+            chip.run_until_breakpoint(100).expect("emulation error"),
+            Some(0x200)
+        );
+        assert_eq!(chip.instruction_count(), 0);
+        assert_eq!(
+            chip.run_until_breakpoint(100).expect("emulation error"),
+            Some(0x202)
+        );
+        // stepping away from a breakpoint and back to it stops there again
+        chip.step().expect("emulation error");
+        chip.step().expect("emulation error");
+        assert_eq!(
+            chip.run_until_breakpoint(100).expect("emulation error"),
+            Some(0x202)
+        );
+        assert_eq!(chip.get_regs()[0], 1);
+    }
+
+    #[test]
+    fn step_traced() {
+        let mut chip = Chip8::new();
+        // LD V1, 0xFF; ADD V1, V1; LD V1, 0xFE; LD V2, K
+        chip.load_rom(&[0x61, 0xff, 0x81, 0x14, 0x61, 0xfe, 0xf2, 0x0a])
+            .expect("error loading rom");
+
+        let info = chip.step_traced().expect("emulation error");
+        assert_eq!(
+            info,
+            StepInfo {
+                pc: 0x200,
+                opcode: 0x61ff,
+                mnemonic: "LD",
+                written_regs: vec![1],
+            }
+        );
+        let info = chip.step_traced().expect("emulation error");
+        assert_eq!((info.pc, info.mnemonic), (0x202, "ADD"));
+        assert_eq!(info.written_regs, [1, 0xf]);
+        // same value
+        let info = chip.step_traced().expect("emulation error");
+        assert_eq!(info.written_regs, [1]);
+        // no key released, so FX0A waits
+        let info = chip.step_traced().expect("emulation error");
+        assert_eq!((info.pc, info.mnemonic), (0x206, "LD"));
+        assert!(info.written_regs.is_empty());
+    }
+
+    #[test]
+    fn set_registers() {
+        let mut chip = Chip8::new();
+        chip.set_reg(0xf, 0x01).expect("debug error");
+        assert_eq!(chip.v[0xf], 0x01);
+        assert!(matches!(
+            chip.set_reg(0x10, 0xff),
+            Err(DebugChipError::NoRegister(0x10))
+        ));
+        assert!(chip.set_reg(0x11, 0xff).is_err());
+    }
+
+    #[test]
+    fn summary() {
+        let mut chip = Chip8::new();
+        chip.set_reg(3, 0x1f).expect("debug error");
+        chip.set_dt(9);
+        chip.key_down(0xa);
+
+        let summary = chip.summary();
+        let lines: Vec<&str> = summary.lines().collect();
+        let regs: Vec<u8> = lines[0]
+            .split(' ')
+            .map(|r| u8::from_str_radix(&r[3..], 16).expect("bad register"))
+            .collect();
+        assert_eq!(regs.len(), 0x10);
+        assert_eq!(regs[3], 0x1f);
+        assert_eq!(lines[1], "PC=0x200 I=0x000 SP=0 DT=9 ST=0");
+        assert_eq!(lines[2], "KEYS=..........A.....");
+    }
+
+    #[test]
+    fn get_pixel() {
+        let mut chip = Chip8::new();
+        chip.debug_draw_sprite(62, 3, &[0b1010_0000])
+            .expect("debug error");
+        assert_eq!(chip.get_pixel(62, 3).ok(), Some(true));
+        assert_eq!(chip.get_pixel(63, 3).ok(), Some(false));
+        assert_eq!(chip.get_pixel(62, 4).ok(), Some(false));
+        // clipped, not wrapped
+        assert_eq!(chip.get_pixel(0, 3).ok(), Some(false));
+        assert!(chip.get_pixel(64, 3).is_err());
+    }
+
+    #[test]
+    fn crash_report() {
+        // LD V3, 7; CALL 0x206; (invalid) 0xFFFF; ADD V3, 1; (invalid) 0xE000
+        let rom = [0x63, 0x07, 0x22, 0x06, 0xff, 0xff, 0x73, 0x01, 0xe0, 0x00];
+        let mut chip = Chip8::new();
+        chip.load_rom(&rom).expect("error loading rom");
+        chip.set_history_depth(8);
+        assert!(chip.run(10, None).is_err());
+        assert_eq!(chip.crash_report(), None);
+
+        let mut chip = Chip8::new();
+        chip.load_rom(&rom).expect("error loading rom");
+        chip.set_history_depth(8);
+        chip.set_crash_reports(true);
+        assert!(chip.run(10, None).is_err());
+
+        let report = chip.crash_report().expect("no crash report");
+        assert_eq!(report.pc, 0x208);
+        assert_eq!(report.opcode, Some(0xe000));
+        assert_eq!(report.regs[3], 8);
+        assert_eq!(report.sp, 1);
+        assert_eq!(report.stack[0], 0x202);
+        assert_eq!(
+            report.history,
+            [
+                (0x200, 0x6307),
+                (0x202, 0x2206),
+                (0x206, 0x7301),
+                (0x208, 0xe000)
+            ]
+        );
+        assert_eq!(report.error, "Unrecognized opcode: 0xE000");
+    }
+
+    #[test]
+    fn schip_warnings() {
+        // HIGH; HIGH; SCR
+        let rom = [0x00, 0xff, 0x00, 0xff, 0x00, 0xfb];
+        let mut chip = Chip8::new();
+        chip.load_rom(&rom).expect("error loading rom");
+        chip.run(2, None).expect("emulation error");
+        assert!(chip.step().is_err());
+        assert!(chip.take_warnings().is_empty());
+
+        let mut chip = Chip8::new();
+        chip.load_rom(&rom).expect("error loading rom");
+        chip.set_schip_warnings(true);
+        chip.run(2, None).expect("emulation error");
+        assert!(chip.hires);
+        assert!(chip.step().is_err());
+        assert_eq!(
+            chip.take_warnings(),
+            [
+                "the SUPER-CHIP instruction HIGH (0x00FF) at 0x200 is not supported on Chip8",
+                "the SUPER-CHIP instruction SCR (0x00FB) at 0x204 is not supported on Chip8",
+            ]
+        );
+        assert!(chip.take_warnings().is_empty());
+
+        let mut chip = Chip8::new();
+        chip.load_rom(&rom).expect("error loading rom");
+        chip.set_schip_warnings(true);
+        chip.set_platform(Platform::SuperChip);
+        chip.run(2, None).expect("emulation error");
+        assert!(chip.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn clear_region() {
+        let mut chip = Chip8::new();
+        for y in 0..32 {
+            for x in 0..64 {
+                chip.set_fb(x, y, true).expect("debug error");
+            }
+        }
+        chip.clear_region(10, 5, 4, 3).expect("debug error");
+
+        for y in 0..32 {
+            for x in 0..64 {
+                let cleared = (10..14).contains(&x) && (5..8).contains(&y);
+                assert_eq!(chip.get_pixel(x, y).expect("debug error"), !cleared);
+            }
+        }
+        assert!(chip.clear_region(60, 0, 5, 1).is_err());
+        assert!(chip.clear_region(0, 30, 1, 3).is_err());
+
+        // empty rectangles clear nothing, but must still be on the screen
+        chip.clear_region(5, 5, 0, 3).expect("debug error");
+        chip.clear_region(5, 5, 3, 0).expect("debug error");
+        assert!(chip.get_pixel(5, 5).expect("debug error"));
+        assert!(matches!(
+            chip.clear_region(100, 0, 0, 0),
+            Err(DebugChipError::NoPixel(100, 0))
+        ));
+        assert!(matches!(
+            chip.clear_region(0, 40, 0, 0),
+            Err(DebugChipError::NoPixel(0, 40))
+        ));
+        assert!(matches!(
+            chip.clear_region(60, 0, 10, 0),
+            Err(DebugChipError::NoPixel(69, 0))
+        ));
+        chip.clear_region(0, 0, 64, 32).expect("debug error");
+        assert!(chip.fb_flat().iter().all(|&p| !p));
+    }
+
+    #[test]
+    fn high_resolution() {
+        let mut chip = Chip8::new();
+        assert_eq!(chip.dimensions(), (64, 32));
+
+        chip.set_resolution(true);
+        assert_eq!(chip.dimensions(), (128, 64));
+        chip.set_fb(127, 63, true).expect("debug error");
+        assert!(chip.debug_draw_sprite(120, 60, &[0x01]).is_ok());
+        assert!(chip.fb().nth(60).expect("no row")[127]);
+        assert_eq!(chip.save_display().len(), 128 * 64);
+
+        chip.set_resolution(false);
+        assert_eq!(chip.fb().len(), 32);
+        assert!(chip.set_fb(127, 63, true).is_err());
+    }
+
+    #[test]
+    fn save_and_restore_display() {
+        let mut chip = Chip8::new();
+        chip.debug_draw_sprite(10, 5, &[0xa5, 0x5a])
+            .expect("debug error");
+        chip.set_fb(63, 31, true).expect("debug error");
+        let saved = chip.save_display();
+        let fb = chip.fb_flat().to_vec();
+
+        chip.debug_draw_sprite(8, 4, &[0xff; 5])
+            .expect("debug error");
+        assert_ne!(chip.fb_flat(), fb);
+        chip.restore_display(&saved).expect("debug error");
+        assert_eq!(chip.fb_flat(), fb);
+
+        assert!(chip.restore_display(&saved[1..]).is_err());
+    }
+
+    #[test]
+    fn search_memory() {
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xde])
+            .expect("error loading rom");
+        chip.set_mem(0xffe, 0xde).expect("debug error");
+        chip.set_mem(0xfff, 0xad).expect("debug error");
+
+        assert_eq!(chip.search_memory(&[0xde, 0xad]), vec![0x200, 0x204, 0xffe]);
+        assert_eq!(chip.search_memory(&[0xbe, 0xef, 0xde]), vec![0x202]);
+        assert!(chip.search_memory(&[0xde, 0xad, 0xde, 0xad]).is_empty());
+        assert!(chip.search_memory(&[]).is_empty());
+    }
+
+    #[test]
+    fn queue_opcodes() {
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0x63, 0x01]).expect("error loading rom");
+        chip.queue_opcodes(&[0x6105, 0x6207]).expect("debug error");
+
+        chip.step().expect("emulation error");
+        chip.step().expect("emulation error");
+        assert_eq!(chip.get_regs()[1], 0x05);
+        assert_eq!(chip.get_regs()[2], 0x07);
+
+        chip.step().expect("emulation error");
+        assert_eq!(chip.get_pointers().0, 0x200);
+
+        chip.step().expect("emulation error");
+        assert_eq!(chip.get_regs()[3], 0x01);
+    }
+
+    #[test]
+    fn queue_too_many_opcodes() {
+        let mut chip = Chip8::new();
+        assert!(chip.queue_opcodes(&[0x6000; 0x80]).is_err());
+    }
 }
@@ -0,0 +1,246 @@
+/// A decoded instruction.
+///
+/// Register operands are indices in the register array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `0NNN`: call the machine code routine at `NNN`.
+    Sys(u16),
+    /// `00CN`: scroll down `N` lines (SUPER-CHIP).
+    Scd(u8),
+    /// `00E0`
+    Cls,
+    /// `00EE`
+    Ret,
+    /// `00FB`: scroll right 4 pixels (SUPER-CHIP).
+    Scr,
+    /// `00FC`: scroll left 4 pixels (SUPER-CHIP).
+    Scl,
+    /// `00FD`: exit the interpreter (SUPER-CHIP).
+    Exit,
+    /// `00FE`: switch to the low resolution (SUPER-CHIP).
+    Low,
+    /// `00FF`: switch to the high resolution (SUPER-CHIP).
+    High,
+    /// `1NNN`
+    Jp(u16),
+    /// `2NNN`
+    Call(u16),
+    /// `3XKK`
+    SeByte(usize, u8),
+    /// `4XKK`
+    SneByte(usize, u8),
+    /// `5XY0`
+    SeReg(usize, usize),
+    /// `6XKK`
+    LdByte(usize, u8),
+    /// `7XKK`
+    AddByte(usize, u8),
+    /// `8XY0`
+    LdReg(usize, usize),
+    /// `8XY1`
+    Or(usize, usize),
+    /// `8XY2`
+    And(usize, usize),
+    /// `8XY3`
+    Xor(usize, usize),
+    /// `8XY4`
+    AddReg(usize, usize),
+    /// `8XY5`
+    Sub(usize, usize),
+    /// `8XY6`
+    Shr(usize, usize),
+    /// `8XY7`
+    Subn(usize, usize),
+    /// `8XYE`
+    Shl(usize, usize),
+    /// `9XY0`
+    SneReg(usize, usize),
+    /// `ANNN`
+    LdI(u16),
+    /// `BNNN`
+    JpV0(u16),
+    /// `CXKK`
+    Rnd(usize, u8),
+    /// `DXYN`, or `DXY0` for a 16x16 sprite on SUPER-CHIP.
+    Drw(usize, usize, usize),
+    /// `EX9E`
+    Skp(usize),
+    /// `EXA1`
+    Sknp(usize),
+    /// `FX07`
+    LdVxDt(usize),
+    /// `FX0A`
+    LdVxK(usize),
+    /// `FX15`
+    LdDtVx(usize),
+    /// `FX18`
+    LdStVx(usize),
+    /// `FX1E`
+    AddI(usize),
+    /// `FX29`
+    LdF(usize),
+    /// `FX30`: point `I` to the big font sprite of `Vx` (SUPER-CHIP).
+    LdHf(usize),
+    /// `FX33`
+    LdB(usize),
+    /// `FX55`
+    LdIVx(usize),
+    /// `FX65`
+    LdVxI(usize),
+    /// `FX75`: save `V0` to `Vx` in the flag registers (SUPER-CHIP).
+    LdRVx(usize),
+    /// `FX85`: load `V0` to `Vx` from the flag registers (SUPER-CHIP).
+    LdVxR(usize),
+}
+
+impl Instruction {
+    /// Returns true if the instruction was added by SUPER-CHIP.
+    pub fn is_schip(self) -> bool {
+        use Instruction::*;
+        matches!(
+            self,
+            Scd(_) | Scr | Scl | Exit | Low | High | Drw(_, _, 0) | LdHf(_) | LdRVx(_) | LdVxR(_)
+        )
+    }
+
+    /// Returns the mnemonic of the instruction, like `LD` or `DRW`.
+    /// Variants of the same operation share it.
+    pub fn mnemonic(self) -> &'static str {
+        use Instruction::*;
+        match self {
+            Sys(_) => "SYS",
+            Scd(_) => "SCD",
+            Cls => "CLS",
+            Ret => "RET",
+            Scr => "SCR",
+            Scl => "SCL",
+            Exit => "EXIT",
+            Low => "LOW",
+            High => "HIGH",
+            Jp(_) | JpV0(_) => "JP",
+            Call(_) => "CALL",
+            SeByte(..) | SeReg(..) => "SE",
+            SneByte(..) | SneReg(..) => "SNE",
+            LdByte(..) | LdReg(..) | LdI(_) | LdVxDt(_) | LdVxK(_) | LdDtVx(_) | LdStVx(_)
+            | LdF(_) | LdHf(_) | LdB(_) | LdIVx(_) | LdVxI(_) | LdRVx(_) | LdVxR(_) => "LD",
+            AddByte(..) | AddReg(..) | AddI(_) => "ADD",
+            Or(..) => "OR",
+            And(..) => "AND",
+            Xor(..) => "XOR",
+            Sub(..) => "SUB",
+            Shr(..) => "SHR",
+            Subn(..) => "SUBN",
+            Shl(..) => "SHL",
+            Rnd(..) => "RND",
+            Drw(..) => "DRW",
+            Skp(_) => "SKP",
+            Sknp(_) => "SKNP",
+        }
+    }
+}
+
+/// Decodes an opcode.
+/// Returns `None` if the opcode is not a valid instruction.
+pub fn decode(op: u16) -> Option<Instruction> {
+    use Instruction::*;
+
+    let x = ((op >> 8) & 0xf) as usize;
+    let y = ((op >> 4) & 0xf) as usize;
+    let n = (op & 0xf) as usize;
+    let kk = op as u8;
+    let nnn = op & 0x0fff;
+
+    let ins = match op & 0xf000 {
+        0x0000 => match op {
+            0x00c0..=0x00cf => Scd(n as u8),
+            0x00e0 => Cls,
+            0x00ee => Ret,
+            0x00fb => Scr,
+            0x00fc => Scl,
+            0x00fd => Exit,
+            0x00fe => Low,
+            0x00ff => High,
+            _ => Sys(nnn),
+        },
+        0x1000 => Jp(nnn),
+        0x2000 => Call(nnn),
+        0x3000 => SeByte(x, kk),
+        0x4000 => SneByte(x, kk),
+        0x5000 if n == 0 => SeReg(x, y),
+        0x6000 => LdByte(x, kk),
+        0x7000 => AddByte(x, kk),
+        0x8000 => match n {
+            0x0 => LdReg(x, y),
+            0x1 => Or(x, y),
+            0x2 => And(x, y),
+            0x3 => Xor(x, y),
+            0x4 => AddReg(x, y),
+            0x5 => Sub(x, y),
+            0x6 => Shr(x, y),
+            0x7 => Subn(x, y),
+            0xe => Shl(x, y),
+            _ => return None,
+        },
+        0x9000 if n == 0 => SneReg(x, y),
+        0xa000 => LdI(nnn),
+        0xb000 => JpV0(nnn),
+        0xc000 => Rnd(x, kk),
+        0xd000 => Drw(x, y, n),
+        0xe000 => match kk {
+            0x9e => Skp(x),
+            0xa1 => Sknp(x),
+            _ => return None,
+        },
+        0xf000 => match kk {
+            0x07 => LdVxDt(x),
+            0x0a => LdVxK(x),
+            0x15 => LdDtVx(x),
+            0x18 => LdStVx(x),
+            0x1e => AddI(x),
+            0x29 => LdF(x),
+            0x30 => LdHf(x),
+            0x33 => LdB(x),
+            0x55 => LdIVx(x),
+            0x65 => LdVxI(x),
+            0x75 => LdRVx(x),
+            0x85 => LdVxR(x),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(ins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_opcodes() {
+        assert_eq!(decode(0x00e0), Some(Instruction::Cls));
+        assert_eq!(decode(0x0123), Some(Instruction::Sys(0x123)));
+        assert_eq!(decode(0x8ab4), Some(Instruction::AddReg(0xa, 0xb)));
+        assert_eq!(decode(0xd125), Some(Instruction::Drw(1, 2, 5)));
+        assert_eq!(decode(0xf365), Some(Instruction::LdVxI(3)));
+        assert_eq!(decode(0x5121), None);
+        assert_eq!(decode(0x8008), None);
+        assert_eq!(decode(0xe000), None);
+        assert_eq!(decode(0xf0ff), None);
+    }
+
+    #[test]
+    fn decode_schip_opcodes() {
+        assert_eq!(decode(0x00c5), Some(Instruction::Scd(5)));
+        assert_eq!(decode(0x00fb), Some(Instruction::Scr));
+        assert_eq!(decode(0x00fc), Some(Instruction::Scl));
+        assert_eq!(decode(0x00fd), Some(Instruction::Exit));
+        assert_eq!(decode(0x00fe), Some(Instruction::Low));
+        assert_eq!(decode(0x00ff), Some(Instruction::High));
+        assert_eq!(decode(0xf230), Some(Instruction::LdHf(2)));
+        assert_eq!(decode(0xf775), Some(Instruction::LdRVx(7)));
+        assert_eq!(decode(0xf785), Some(Instruction::LdVxR(7)));
+        assert!(decode(0xd120).is_some_and(Instruction::is_schip));
+        assert!(!decode(0xd121).is_some_and(Instruction::is_schip));
+    }
+}
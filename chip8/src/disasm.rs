@@ -0,0 +1,321 @@
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::constants::MEM_SIZE;
+use crate::decode::{decode, Instruction};
+use crate::Chip8;
+
+/// Returns the assembly for an opcode, like `LD V2, 0x1C`.
+///
+/// Opcodes that are not valid instructions are shown as data, like `DB 0xFFFF`.
+pub fn disassemble(op: u16) -> String {
+    use Instruction::*;
+
+    let ins = match decode(op) {
+        Some(ins) => ins,
+        None => return format!("DB {:#06X}", op),
+    };
+
+    match ins {
+        Sys(addr) => format!("SYS {:#05X}", addr),
+        Scd(n) => format!("SCD {}", n),
+        Cls => "CLS".to_string(),
+        Ret => "RET".to_string(),
+        Scr => "SCR".to_string(),
+        Scl => "SCL".to_string(),
+        Exit => "EXIT".to_string(),
+        Low => "LOW".to_string(),
+        High => "HIGH".to_string(),
+        Jp(addr) => format!("JP {:#05X}", addr),
+        Call(addr) => format!("CALL {:#05X}", addr),
+        SeByte(x, kk) => format!("SE V{:X}, {:#04X}", x, kk),
+        SneByte(x, kk) => format!("SNE V{:X}, {:#04X}", x, kk),
+        SeReg(x, y) => format!("SE V{:X}, V{:X}", x, y),
+        LdByte(x, kk) => format!("LD V{:X}, {:#04X}", x, kk),
+        AddByte(x, kk) => format!("ADD V{:X}, {:#04X}", x, kk),
+        LdReg(x, y) => format!("LD V{:X}, V{:X}", x, y),
+        Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+        And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+        Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+        AddReg(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+        Sub(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+        Shr(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+        Subn(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+        Shl(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+        SneReg(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+        LdI(addr) => format!("LD I, {:#05X}", addr),
+        JpV0(addr) => format!("JP V0, {:#05X}", addr),
+        Rnd(x, kk) => format!("RND V{:X}, {:#04X}", x, kk),
+        Drw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Skp(x) => format!("SKP V{:X}", x),
+        Sknp(x) => format!("SKNP V{:X}", x),
+        LdVxDt(x) => format!("LD V{:X}, DT", x),
+        LdVxK(x) => format!("LD V{:X}, K", x),
+        LdDtVx(x) => format!("LD DT, V{:X}", x),
+        LdStVx(x) => format!("LD ST, V{:X}", x),
+        AddI(x) => format!("ADD I, V{:X}", x),
+        LdF(x) => format!("LD F, V{:X}", x),
+        LdHf(x) => format!("LD HF, V{:X}", x),
+        LdB(x) => format!("LD B, V{:X}", x),
+        LdIVx(x) => format!("LD [I], V{:X}", x),
+        LdVxI(x) => format!("LD V{:X}, [I]", x),
+        LdRVx(x) => format!("LD R, V{:X}", x),
+        LdVxR(x) => format!("LD V{:X}, R", x),
+    }
+}
+
+impl Chip8 {
+    /// Returns what an opcode does in plain English, with its operands filled in,
+    /// like `Set V3 to V3 + V5, set VF on carry`.
+    ///
+    /// Returns `None` if the opcode is not a valid instruction.
+    pub fn explain(op: u16) -> Option<String> {
+        use Instruction::*;
+
+        let text = match decode(op)? {
+            Sys(addr) => format!("Call the machine code routine at {:#05X}", addr),
+            Scd(n) => format!("Scroll the screen down {} lines", n),
+            Cls => "Clear the screen".to_string(),
+            Ret => "Return from the current subroutine".to_string(),
+            Scr => "Scroll the screen right 4 pixels".to_string(),
+            Scl => "Scroll the screen left 4 pixels".to_string(),
+            Exit => "Exit the interpreter".to_string(),
+            Low => "Switch to the 64x32 resolution".to_string(),
+            High => "Switch to the 128x64 resolution".to_string(),
+            Jp(addr) => format!("Jump to {:#05X}", addr),
+            Call(addr) => format!("Call the subroutine at {:#05X}", addr),
+            SeByte(x, kk) => format!("Skip the next instruction if V{:X} is {}", x, kk),
+            SneByte(x, kk) => format!("Skip the next instruction if V{:X} is not {}", x, kk),
+            SeReg(x, y) => format!("Skip the next instruction if V{:X} equals V{:X}", x, y),
+            LdByte(x, kk) => format!("Set V{:X} to {}", x, kk),
+            AddByte(x, kk) => format!("Add {} to V{:X}, without carry", kk, x),
+            LdReg(x, y) => format!("Set V{:X} to V{:X}", x, y),
+            Or(x, y) => format!("Set V{:X} to V{:X} OR V{:X}", x, x, y),
+            And(x, y) => format!("Set V{:X} to V{:X} AND V{:X}", x, x, y),
+            Xor(x, y) => format!("Set V{:X} to V{:X} XOR V{:X}", x, x, y),
+            AddReg(x, y) => format!("Set V{:X} to V{:X} + V{:X}, set VF on carry", x, x, y),
+            Sub(x, y) => format!(
+                "Set V{:X} to V{:X} - V{:X}, set VF if there is no borrow",
+                x, x, y
+            ),
+            Shr(x, _) => format!("Shift V{:X} right by one, set VF to the bit shifted out", x),
+            Subn(x, y) => format!(
+                "Set V{:X} to V{:X} - V{:X}, set VF if there is no borrow",
+                x, y, x
+            ),
+            Shl(x, _) => format!("Shift V{:X} left by one, set VF to the bit shifted out", x),
+            SneReg(x, y) => format!(
+                "Skip the next instruction if V{:X} does not equal V{:X}",
+                x, y
+            ),
+            LdI(addr) => format!("Set I to {:#05X}", addr),
+            JpV0(addr) => format!("Jump to {:#05X} + V0", addr),
+            Rnd(x, kk) => format!("Set V{:X} to a random number AND {:#04X}", x, kk),
+            Drw(x, y, 0) => format!(
+                "Draw the 16x16 sprite at I at (V{:X}, V{:X}), set VF on collision",
+                x, y
+            ),
+            Drw(x, y, n) => format!(
+                "Draw the {} bytes sprite at I at (V{:X}, V{:X}), set VF on collision",
+                n, x, y
+            ),
+            Skp(x) => format!(
+                "Skip the next instruction if the key in V{:X} is pressed",
+                x
+            ),
+            Sknp(x) => format!(
+                "Skip the next instruction if the key in V{:X} is not pressed",
+                x
+            ),
+            LdVxDt(x) => format!("Set V{:X} to the delay timer", x),
+            LdVxK(x) => format!("Wait for a key press, and store the key in V{:X}", x),
+            LdDtVx(x) => format!("Set the delay timer to V{:X}", x),
+            LdStVx(x) => format!("Set the sound timer to V{:X}", x),
+            AddI(x) => format!("Add V{:X} to I", x),
+            LdF(x) => format!("Point I to the font sprite of the digit in V{:X}", x),
+            LdHf(x) => format!("Point I to the big font sprite of the digit in V{:X}", x),
+            LdB(x) => format!("Store the decimal digits of V{:X} at I, I + 1 and I + 2", x),
+            LdIVx(x) => format!("Store V0 to V{:X} in memory, starting at I", x),
+            LdVxI(x) => format!("Load V0 to V{:X} from memory, starting at I", x),
+            LdRVx(x) => format!("Save V0 to V{:X} in the flag registers", x),
+            LdVxR(x) => format!("Load V0 to V{:X} from the flag registers", x),
+        };
+        Some(text)
+    }
+
+    /// Returns the assembly for the opcode at `addr` in memory,
+    /// like the upcoming instruction at `pc`.
+    ///
+    /// The last byte of memory, with no room for an opcode, is shown as data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `addr` is past the end of memory.
+    pub fn disassemble_at(&self, addr: usize) -> String {
+        if addr == MEM_SIZE - 1 {
+            return format!("DB {:#04X}", self.mem[addr]);
+        }
+        disassemble(u16::from_be_bytes([self.mem[addr], self.mem[addr + 1]]))
+    }
+}
+
+/// Returns the name of the label at `addr`, like `L_2A0`.
+fn label(addr: u16) -> String {
+    format!("L_{:03X}", addr)
+}
+
+/// Returns the assembly for a whole rom loaded at `base`, one instruction per line.
+///
+/// The targets of jumps and calls get a label, like `L_2A0:`,
+/// and the instructions refer to them by name.
+/// A trailing odd byte is shown as data.
+pub fn listing(rom: &[u8], base: u16) -> String {
+    let ops: Vec<u16> = rom
+        .chunks_exact(2)
+        .map(|pair| (pair[0] as u16) << 8 | pair[1] as u16)
+        .collect();
+    let end = base as usize + ops.len() * 2;
+
+    // first pass: only targets at the start of an instruction get a label
+    let targets: BTreeSet<u16> = ops
+        .iter()
+        .filter_map(|&op| match decode(op) {
+            Some(Instruction::Jp(addr) | Instruction::Call(addr)) => Some(addr),
+            _ => None,
+        })
+        .filter(|&addr| addr >= base && (addr as usize) < end && (addr - base).is_multiple_of(2))
+        .collect();
+
+    let mut out = String::new();
+    for (n, &op) in ops.iter().enumerate() {
+        let addr = base + n as u16 * 2;
+        if targets.contains(&addr) {
+            writeln!(out, "{}:", label(addr)).unwrap();
+        }
+        let line = match decode(op) {
+            Some(Instruction::Jp(target)) if targets.contains(&target) => {
+                format!("JP {}", label(target))
+            }
+            Some(Instruction::Call(target)) if targets.contains(&target) => {
+                format!("CALL {}", label(target))
+            }
+            _ => disassemble(op),
+        };
+        writeln!(out, "    {}", line).unwrap();
+    }
+    if let Some(byte) = rom.chunks_exact(2).remainder().first() {
+        writeln!(out, "    DB {:#04X}", byte).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_opcodes() {
+        assert_eq!(disassemble(0x1321), "JP 0x321");
+        assert_eq!(disassemble(0x621c), "LD V2, 0x1C");
+        assert_eq!(disassemble(0xd125), "DRW V1, V2, 5");
+        assert_eq!(disassemble(0xe4a1), "SKNP V4");
+        assert_eq!(disassemble(0xffff), "DB 0xFFFF");
+
+        // one of each family
+        let ops = [
+            (0x00e0, "CLS"),
+            (0x1321, "JP 0x321"),
+            (0x2abc, "CALL 0xABC"),
+            (0x3a05, "SE VA, 0x05"),
+            (0x4b10, "SNE VB, 0x10"),
+            (0x5120, "SE V1, V2"),
+            (0x621c, "LD V2, 0x1C"),
+            (0x7e01, "ADD VE, 0x01"),
+            (0x8a34, "ADD VA, V3"),
+            (0x9c50, "SNE VC, V5"),
+            (0xa2f0, "LD I, 0x2F0"),
+            (0xb300, "JP V0, 0x300"),
+            (0xc3ff, "RND V3, 0xFF"),
+            (0xd125, "DRW V1, V2, 5"),
+            (0xe79e, "SKP V7"),
+            (0xf633, "LD B, V6"),
+        ];
+        for (op, asm) in ops {
+            assert_eq!(disassemble(op), asm);
+        }
+    }
+
+    #[test]
+    fn disassemble_memory() {
+        let mut chip = Chip8::new();
+        chip.load_rom(&[0x62, 0x1c, 0xd1, 0x25])
+            .expect("error loading rom");
+        assert_eq!(chip.disassemble_at(0x200), "LD V2, 0x1C");
+        assert_eq!(chip.disassemble_at(0x202), "DRW V1, V2, 5");
+        // any address, even in the middle of an instruction
+        assert_eq!(chip.disassemble_at(0x201), "JP 0xCD1");
+        assert_eq!(chip.disassemble_at(MEM_SIZE - 1), "DB 0x00");
+    }
+
+    #[test]
+    fn explain_opcodes() {
+        assert_eq!(
+            Chip8::explain(0x8354).as_deref(),
+            Some("Set V3 to V3 + V5, set VF on carry")
+        );
+        assert_eq!(Chip8::explain(0x6a2c).as_deref(), Some("Set VA to 44"));
+        assert_eq!(
+            Chip8::explain(0x2400).as_deref(),
+            Some("Call the subroutine at 0x400")
+        );
+        assert_eq!(
+            Chip8::explain(0x8127).as_deref(),
+            Some("Set V1 to V2 - V1, set VF if there is no borrow")
+        );
+        assert_eq!(
+            Chip8::explain(0xd015).as_deref(),
+            Some("Draw the 5 bytes sprite at I at (V0, V1), set VF on collision")
+        );
+        assert_eq!(
+            Chip8::explain(0xe29e).as_deref(),
+            Some("Skip the next instruction if the key in V2 is pressed")
+        );
+        assert_eq!(Chip8::explain(0xffff), None);
+    }
+
+    #[test]
+    fn listing_with_labels() {
+        // LD V0, 0; ADD V0, 1; CALL 0x208; JP 0x202; RET; (data) 0xAB
+        let rom = [
+            0x60, 0x00, 0x70, 0x01, 0x22, 0x08, 0x12, 0x02, 0x00, 0xee, 0xab,
+        ];
+        let expected = [
+            "    LD V0, 0x00",
+            "L_202:",
+            "    ADD V0, 0x01",
+            "    CALL L_208",
+            "    JP L_202",
+            "L_208:",
+            "    RET",
+            "    DB 0xAB",
+        ];
+        assert_eq!(listing(&rom, 0x200).lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn disassemble_schip_opcodes() {
+        assert_eq!(disassemble(0x00c4), "SCD 4");
+        assert_eq!(disassemble(0x00fb), "SCR");
+        assert_eq!(disassemble(0x00fc), "SCL");
+        assert_eq!(disassemble(0x00fd), "EXIT");
+        assert_eq!(disassemble(0x00fe), "LOW");
+        assert_eq!(disassemble(0x00ff), "HIGH");
+        assert_eq!(disassemble(0xd340), "DRW V3, V4, 0");
+        assert_eq!(disassemble(0xf530), "LD HF, V5");
+        assert_eq!(disassemble(0xf675), "LD R, V6");
+        assert_eq!(disassemble(0xf685), "LD V6, R");
+    }
+}
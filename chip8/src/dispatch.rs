@@ -0,0 +1,294 @@
+use crate::error::ChipError;
+use crate::{hi_nib, lo_nib, Chip8};
+
+/// How the machine finds the operation to run for an opcode.
+///
+/// Both ways behave exactly the same, only their speed differs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dispatch {
+    /// A `match` on the opcode.
+    #[default]
+    Match,
+    /// A table of functions indexed by the high nibble of the opcode,
+    /// with a second table for the `8XYN` operations.
+    Table,
+}
+
+type Handler = fn(&mut Chip8, u16) -> Result<(), ChipError>;
+
+/// Handlers by high nibble.
+const TABLE: [Handler; 16] = [
+    op_0, op_1, op_2, op_3, op_4, op_5, op_6, op_7, op_8, op_9, op_a, op_b, op_c, op_d, op_e, op_f,
+];
+
+/// Handlers of `8XYN`, by `N`.
+const TABLE_8: [Handler; 16] = [
+    op_8xy0,
+    op_8xy1,
+    op_8xy2,
+    op_8xy3,
+    op_8xy4,
+    op_8xy5,
+    op_8xy6,
+    op_8xy7,
+    unrecognized,
+    unrecognized,
+    unrecognized,
+    unrecognized,
+    unrecognized,
+    unrecognized,
+    op_8xye,
+    unrecognized,
+];
+
+fn x(op: u16) -> usize {
+    lo_nib((op >> 8) as u8) as usize
+}
+
+fn y(op: u16) -> usize {
+    hi_nib(op as u8) as usize
+}
+
+fn n(op: u16) -> usize {
+    lo_nib(op as u8) as usize
+}
+
+fn byte(op: u16) -> u8 {
+    op as u8
+}
+
+fn nnn(op: u16) -> u16 {
+    op & 0x0fff
+}
+
+fn unrecognized(_: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    Err(ChipError::UnrecognizedOpcode(op))
+}
+
+fn op_0(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    match op {
+        0x00e0 => chip.opcode_cls(),
+        0x00ee => chip.opcode_ret()?,
+        0x00fe => chip.opcode_low(),
+        0x00ff => chip.opcode_high(),
+        0x0000 if chip.quirks.treat_zero_as_nop => {}
+        _ => chip.opcode_sys(nnn(op))?,
+    }
+    Ok(())
+}
+
+fn op_1(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_jp(nnn(op))
+}
+
+fn op_2(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_call(nnn(op))
+}
+
+fn op_3(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_se(x(op), byte(op));
+    Ok(())
+}
+
+fn op_4(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_sne(x(op), byte(op));
+    Ok(())
+}
+
+fn op_5(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    if n(op) != 0 {
+        return Err(ChipError::UnrecognizedOpcode(op));
+    }
+    chip.opcode_se_r(x(op), y(op));
+    Ok(())
+}
+
+fn op_6(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_ld(x(op), byte(op));
+    Ok(())
+}
+
+fn op_7(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_add(x(op), byte(op));
+    Ok(())
+}
+
+fn op_8(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    TABLE_8[n(op)](chip, op)
+}
+
+fn op_8xy0(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_ld_r(x(op), y(op));
+    Ok(())
+}
+
+fn op_8xy1(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_or(x(op), y(op));
+    Ok(())
+}
+
+fn op_8xy2(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_and(x(op), y(op));
+    Ok(())
+}
+
+fn op_8xy3(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_xor(x(op), y(op));
+    Ok(())
+}
+
+fn op_8xy4(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_add_r(x(op), y(op));
+    Ok(())
+}
+
+fn op_8xy5(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_sub(x(op), y(op));
+    Ok(())
+}
+
+fn op_8xy6(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_shr(x(op), y(op));
+    Ok(())
+}
+
+fn op_8xy7(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_subn(x(op), y(op));
+    Ok(())
+}
+
+fn op_8xye(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_shl(x(op), y(op));
+    Ok(())
+}
+
+fn op_9(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    if n(op) != 0 {
+        return Err(ChipError::UnrecognizedOpcode(op));
+    }
+    chip.opcode_sne_r(x(op), y(op));
+    Ok(())
+}
+
+fn op_a(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_ld_i(nnn(op));
+    Ok(())
+}
+
+fn op_b(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_jp_r(nnn(op))
+}
+
+fn op_c(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_rnd(x(op), byte(op));
+    Ok(())
+}
+
+fn op_d(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    chip.opcode_drw(x(op), y(op), n(op))
+}
+
+fn op_e(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    match byte(op) {
+        0x9e => chip.opcode_skp(x(op)),
+        0xa1 => chip.opcode_sknp(x(op)),
+        _ => return Err(ChipError::UnrecognizedOpcode(op)),
+    }
+    Ok(())
+}
+
+fn op_f(chip: &mut Chip8, op: u16) -> Result<(), ChipError> {
+    let x = x(op);
+    match byte(op) {
+        0x07 => chip.opcode_ld_dt(x),
+        0x0a => chip.opcode_ld_k(x),
+        0x15 => chip.opcode_ld_dt_r(x),
+        0x18 => chip.opcode_ld_st(x),
+        0x1e => chip.opcode_add_i(x),
+        0x29 => chip.opcode_ld_digit(x),
+        0x33 => chip.opcode_ld_bcd(x)?,
+        0x55 => chip.opcode_ld_mass_store(x)?,
+        0x65 => chip.opcode_ld_mass_load(x)?,
+        _ => return Err(ChipError::UnrecognizedOpcode(op)),
+    }
+    Ok(())
+}
+
+impl Chip8 {
+    /// Returns how opcodes are dispatched.
+    pub fn dispatch(&self) -> Dispatch {
+        self.dispatch
+    }
+
+    /// Sets how opcodes are dispatched.
+    pub fn set_dispatch(&mut self, dispatch: Dispatch) {
+        self.dispatch = dispatch;
+    }
+
+    /// Executes an opcode through the tables, without moving `pc` past it.
+    pub(crate) fn execute_table(&mut self, op: u16) -> Result<(), ChipError> {
+        TABLE[(op >> 12) as usize](self, op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::constants::{MAX_ROM_SIZE, MEM_SIZE};
+    use crate::decode::decode;
+    use crate::platform::Platform;
+    use crate::quirks::Quirks;
+
+    #[test]
+    fn dispatch_matches_decode() {
+        for dispatch in [Dispatch::Match, Dispatch::Table] {
+            for op in 0..=u16::MAX {
+                let mut chip = Chip8::new();
+                let result = match dispatch {
+                    Dispatch::Match => chip.execute(op),
+                    Dispatch::Table => chip.execute_table(op),
+                };
+                let runs = !matches!(result, Err(ChipError::UnrecognizedOpcode(_)));
+                let decoded = decode(op).is_some_and(|ins| Platform::SuperChip.supports(ins));
+                assert_eq!(runs, decoded, "{:?} on {:#06X}", dispatch, op);
+            }
+        }
+    }
+
+    #[test]
+    fn table_matches_match() {
+        let mut rng = StdRng::seed_from_u64(0x696);
+        for seed in 0..100 {
+            let mut rom = vec![0; rng.gen_range(2..=MAX_ROM_SIZE)];
+            rng.fill(&mut rom[..]);
+            let quirks = Quirks::from_bits(rng.gen());
+
+            let mut chips = [Dispatch::Match, Dispatch::Table].map(|dispatch| {
+                let mut chip = Chip8::with_seed(seed);
+                chip.set_quirks(quirks);
+                chip.set_dispatch(dispatch);
+                chip.load_rom(&rom).expect("error loading rom");
+                chip
+            });
+
+            for _ in 0..2000 {
+                let keypad = rng.gen();
+                let restart = (rng.gen_range(0..MEM_SIZE as u16), rng.gen());
+                let results = chips.each_mut().map(|chip| {
+                    chip.keypad = keypad;
+                    let result = chip.step();
+                    if result.is_err() {
+                        // keep going from somewhere else
+                        (chip.pc, chip.i) = restart;
+                    }
+                    result.map_err(|e| e.to_string())
+                });
+                assert_eq!(results[0], results[1]);
+                assert_eq!(chips[0].snapshot(), chips[1].snapshot());
+            }
+        }
+    }
+}
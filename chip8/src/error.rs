@@ -1,23 +1,54 @@
+use crate::constants::{MAX_ROM_SIZE, MEM_SIZE, PROGRAM_OFFSET};
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
 pub enum ChipError {
     PcOutOfBounds(u16),
-    SpOutOfBounds(usize),
+    SpOutOfBounds(u8),
     RomTooBig(usize),
+    LoadAddrTooLow(u16),
+    RomDoesNotFit(u16, usize),
     UnrecognizedOpcode(u16),
+    InvalidPackage(&'static str),
+    AddrOutOfBounds(u16),
+    BadDumpSize(usize),
+    InvalidState(&'static str),
+    NoRewind,
+    NoKey(usize),
+    ZeroTimerRate,
 }
 
-impl std::fmt::Display for ChipError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for ChipError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match &self {
             ChipError::PcOutOfBounds(n) => write!(f, "Program counter out of bounds: {}", n),
             ChipError::SpOutOfBounds(n) => write!(f, "Stack pointer out of bounds: {}", n),
-            ChipError::RomTooBig(n) => write!(f, "Rom too big: {}/3584 bytes", n),
+            ChipError::RomTooBig(n) => write!(f, "Rom too big: {}/{} bytes", n, MAX_ROM_SIZE),
+            ChipError::LoadAddrTooLow(addr) => {
+                write!(
+                    f,
+                    "Load address below {:#06X}: {:#06X}",
+                    PROGRAM_OFFSET, addr
+                )
+            }
+            ChipError::RomDoesNotFit(addr, n) => {
+                write!(f, "Rom of {} bytes doesn't fit at {:#06X}", n, addr)
+            }
             ChipError::UnrecognizedOpcode(op) => write!(f, "Unrecognized opcode: {:#06X}", op),
+            ChipError::InvalidPackage(reason) => write!(f, "Invalid package: {}", reason),
+            ChipError::AddrOutOfBounds(n) => write!(f, "Address out of bounds: {:#06X}", n),
+            ChipError::BadDumpSize(n) => {
+                write!(f, "Bad memory dump size: {}/{} bytes", n, MEM_SIZE)
+            }
+            ChipError::InvalidState(reason) => write!(f, "Invalid save state: {}", reason),
+            ChipError::NoRewind => write!(f, "No earlier state to step back to"),
+            ChipError::NoKey(k) => write!(f, "No such key: {:#03X}", k),
+            ChipError::ZeroTimerRate => write!(f, "Timer rate of 0 Hz"),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for ChipError {}
 
 #[allow(clippy::module_name_repetitions)]
@@ -31,10 +62,12 @@ pub enum DebugChipError {
     NoRegister(usize),
     NoKey(usize),
     NoPixel(usize, usize),
+    ScriptTooLong(usize),
+    BadDisplaySize(usize),
 }
 
-impl std::fmt::Display for DebugChipError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for DebugChipError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match &self {
             DebugChipError::AddrOutOfBounds(n) => write!(f, "address out of bounds: {:#06X}", n),
             DebugChipError::PcOutOfBounds(n) => write!(f, "pc out of bounds: {:#06X}", n),
@@ -46,8 +79,11 @@ impl std::fmt::Display for DebugChipError {
             DebugChipError::NoRegister(n) => write!(f, "no such register: {:#03X}", n),
             DebugChipError::NoKey(n) => write!(f, "no such key: {:#03X}", n),
             DebugChipError::NoPixel(x, y) => write!(f, "pixel out of bounds: ({}, {})", x, y),
+            DebugChipError::ScriptTooLong(n) => write!(f, "too many opcodes: {}", n),
+            DebugChipError::BadDisplaySize(n) => write!(f, "wrong number of pixels: {}", n),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for DebugChipError {}
@@ -0,0 +1,27 @@
+use alloc::boxed::Box;
+use core::fmt;
+
+/// An optional user callback stored in the machine.
+pub(crate) struct Hook<F: ?Sized>(pub(crate) Option<Box<F>>);
+
+impl<F: ?Sized> Default for Hook<F> {
+    fn default() -> Self {
+        Hook(None)
+    }
+}
+
+/// Callbacks can't be cloned, so a cloned hook is always unset.
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Hook(None)
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Hook(set)"),
+            None => write!(f, "Hook(unset)"),
+        }
+    }
+}
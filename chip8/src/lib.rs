@@ -1,13 +1,77 @@
-use rand::{thread_rng, Rng};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::mem::{discriminant, Discriminant};
+use core::ops::Range;
+use core::slice::ChunksExact;
+use core::time::Duration;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+pub mod analysis;
+
+#[cfg(feature = "async")]
+pub mod asynchronous;
+
+pub mod cheat;
+use cheat::Cheat;
+
+mod clock;
+#[cfg(not(feature = "std"))]
+pub use clock::Instant;
+#[cfg(feature = "std")]
+use clock::Instant;
 
 mod constants;
-use constants::{FONT_OFFSET, FONT_SPRITES, MEM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+pub use constants::{DEFAULT_REWIND_DEPTH, DEFAULT_TIMER_HZ, MAX_ROM_SIZE, STACK_DEPTH, VF};
+use constants::{
+    FONT_OFFSET, FONT_SPRITES, HIRES_HEIGHT, HIRES_WIDTH, MEM_SIZE, PROGRAM_OFFSET, SCREEN_HEIGHT,
+    SCREEN_WIDTH,
+};
 
 mod debug;
+use debug::HistoryEntry;
+pub use debug::{CrashReport, StepInfo};
+
+pub mod dispatch;
+use dispatch::Dispatch;
+
+pub mod decode;
+use decode::{decode, Instruction};
+
+pub mod disasm;
+use disasm::disassemble;
+
+mod hook;
+use hook::Hook;
 
 pub mod error;
 use error::ChipError;
 
+pub mod package;
+
+pub mod platform;
+use platform::Platform;
+
+pub mod quirks;
+use quirks::{LdKPolicy, Quirks, StackOverflowPolicy};
+
+#[cfg(feature = "roms")]
+pub mod roms;
+
+#[cfg(feature = "std")]
+pub mod runner;
+
+pub mod snapshot;
+use snapshot::StateSnapshot;
+
 /// Returns the hi nibble (four leftmost bits) of a byte
 fn hi_nib(b: u8) -> u8 {
     (b & 0xf0) >> 4
@@ -18,21 +82,148 @@ fn lo_nib(b: u8) -> u8 {
     b & 0x0f
 }
 
+/// Returns a random seed for `Chip8::new`.
+#[cfg(feature = "std")]
+fn new_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+/// Returns the seed of `Chip8::new` when there is no source of entropy.
+#[cfg(not(feature = "std"))]
+fn new_seed() -> u64 {
+    0
+}
+
+/// A handler for `0NNN`, receiving the machine and `NNN`.
+pub type MachineRoutine = dyn FnMut(&mut Chip8, u16) + Send;
+
+/// A callback receiving the frame buffer at the end of a frame,
+/// as pixels row by row and the width and height of the screen.
+pub type RenderHook = dyn FnMut(&[bool], (usize, usize)) + Send;
+
+/// A callback receiving the address of a jump or call to an odd address,
+/// and its target.
+pub type OddJumpHook = dyn FnMut(u16, u16) + Send;
+
+/// How a bounded run of instructions ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// The run ended normally.
+    Completed,
+    /// The deadline passed before the run could end.
+    DeadlineExceeded,
+    /// The rom reached a jump to itself, the usual way to end a program.
+    /// Only returned by `run`, when enabled with `set_stop_on_self_jump`.
+    Halted,
+}
+
+/// What happened during a frame run by `tick`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TickResult {
+    /// The frame buffer was modified, so the screen needs redrawing.
+    pub framebuffer_changed: bool,
+    /// The buzzer is on at the end of the frame.
+    pub buzzer_on: bool,
+    /// A draw instruction ran.
+    pub drew: bool,
+}
+
+/// When the timers are decremented within a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimerTickPosition {
+    /// Before the first instruction of the frame.
+    #[default]
+    FrameStart,
+    /// After the last instruction of the frame, so the instructions
+    /// of a frame still read the timers set by the previous one.
+    FrameEnd,
+    /// Never, the frontend calls `tick_timers` on its own clock instead,
+    /// to keep the timers at 60Hz whatever the frame rate.
+    Manual,
+}
+
+/// Where a rom was loaded, and how much room is left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadInfo {
+    /// Size of the rom in bytes.
+    pub bytes_loaded: usize,
+    /// Bytes left in memory after the rom.
+    pub free_bytes: usize,
+    /// Address of the first byte of the rom.
+    pub load_address: u16,
+}
+
+/// A write to memory made by an instruction or a cheat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub addr: u16,
+    pub old: u8,
+    pub new: u8,
+    /// Address of the instruction that wrote, or `None` for a cheat.
+    pub pc: Option<u16>,
+}
+
 /// The main structure.
 ///
 /// It manages all the emulation data, and represents the whole backend.
-#[derive(Debug)]
+///
+/// Cloning gives a quick in-memory checkpoint of the machine,
+/// random number generator included.
+/// Callbacks and handlers are not cloned.
+/// They must be `Send`, so the machine can run on another thread.
+#[derive(Debug, Clone)]
 pub struct Chip8 {
     mem: [u8; MEM_SIZE],
-    fb: [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+    fb: Vec<bool>,
+    hires: bool,
     v: [u8; 0x10],
     i: u16,
     dt: u8,
     st: u8,
     pc: u16,
-    sp: usize, // should be u8, but eh
+    sp: u8,
     stack: [u16; 16],
     keypad: [bool; 16],
+    quirks: Quirks,
+    seed: u64,
+    rng: ChaCha12Rng,
+    frame_collision: bool,
+    frame_drew: bool,
+    frame_fb_changed: bool,
+    machine_routine: Hook<MachineRoutine>,
+    fb_dirty: bool,
+    render_hook: Hook<RenderHook>,
+    odd_jump_hook: Hook<OddJumpHook>,
+    history: VecDeque<HistoryEntry>,
+    history_depth: usize,
+    rewind: VecDeque<StateSnapshot>,
+    rewind_depth: usize,
+    record_rewind: bool,
+    cheats: Vec<Cheat>,
+    entry_point: u16,
+    frames: u64,
+    instructions: u64,
+    frame_keypad: [bool; 16],
+    prev_keypad: [bool; 16],
+    key_presses: u64,
+    key_order: [u64; 16],
+    timer_hz: u32,
+    timer_tick_position: TimerTickPosition,
+    rom: Vec<u8>,
+    rom_base: u16,
+    memory_writes: Option<Vec<MemoryWrite>>,
+    dispatch: Dispatch,
+    persistence_frames: u8,
+    persistence: Vec<u8>,
+    stop_on_self_jump: bool,
+    schip_warnings: bool,
+    platform: Platform,
+    warned: Vec<Discriminant<Instruction>>,
+    warnings: Vec<String>,
+    crash_reports: bool,
+    crash_report: Option<CrashReport>,
+    breakpoints: BTreeSet<u16>,
+    stopped_at: Option<u16>,
 }
 
 impl Default for Chip8 {
@@ -46,36 +237,195 @@ impl Chip8 {
     ///
     /// The instance structured already has the font sprites loaded in memory,
     /// and the `pc` register set to `0x200`.
+    ///
+    /// The random number generator gets a random seed, or always the same one
+    /// without the `std` feature, as there is no source of entropy then.
     pub fn new() -> Self {
-        let mut mem = [0; MEM_SIZE];
-        mem[FONT_OFFSET..FONT_OFFSET + FONT_SPRITES.len()].copy_from_slice(&FONT_SPRITES);
+        Self::with_seed(new_seed())
+    }
 
-        Chip8 {
-            mem,
-            fb: [[false; SCREEN_WIDTH]; SCREEN_HEIGHT],
+    /// Returns a new instance whose random number generator uses the given seed.
+    pub fn with_seed(seed: u64) -> Self {
+        let mut chip = Chip8 {
+            mem: [0; MEM_SIZE],
+            fb: Vec::new(),
+            hires: false,
             v: [0; 0x10],
             i: 0,
             dt: 0,
             st: 0,
-            pc: 0x200,
+            pc: PROGRAM_OFFSET as u16,
             sp: 0,
             stack: [0; 16],
             keypad: [false; 16],
-        }
+            quirks: Quirks::default(),
+            seed,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            frame_collision: false,
+            frame_drew: false,
+            frame_fb_changed: false,
+            machine_routine: Hook::default(),
+            fb_dirty: false,
+            render_hook: Hook::default(),
+            odd_jump_hook: Hook::default(),
+            history: VecDeque::new(),
+            history_depth: 0,
+            rewind: VecDeque::new(),
+            rewind_depth: DEFAULT_REWIND_DEPTH,
+            record_rewind: false,
+            cheats: Vec::new(),
+            entry_point: PROGRAM_OFFSET as u16,
+            frames: 0,
+            instructions: 0,
+            frame_keypad: [false; 16],
+            prev_keypad: [false; 16],
+            key_presses: 0,
+            key_order: [0; 16],
+            timer_hz: DEFAULT_TIMER_HZ,
+            timer_tick_position: TimerTickPosition::default(),
+            rom: Vec::new(),
+            rom_base: PROGRAM_OFFSET as u16,
+            memory_writes: None,
+            dispatch: Dispatch::default(),
+            persistence_frames: 0,
+            persistence: Vec::new(),
+            stop_on_self_jump: false,
+            schip_warnings: false,
+            platform: Platform::default(),
+            warned: Vec::new(),
+            warnings: Vec::new(),
+            crash_reports: false,
+            crash_report: None,
+            breakpoints: BTreeSet::new(),
+            stopped_at: None,
+        };
+        chip.reset();
+        chip
     }
 
+    /// Returns a new instance with the given quirks.
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        let mut chip = Self::new();
+        chip.quirks = quirks;
+        chip.reset();
+        chip
+    }
+
+    /// Brings the machine back to its initial state.
+    ///
+    /// Quirks are kept, and the random number generator is seeded again.
     pub fn reset(&mut self) {
-        self.mem = [0; MEM_SIZE];
+        self.reset_keep_display();
+        self.hires = false;
+        self.fb = self.blank_fb();
+        self.persistence.fill(0);
+    }
+
+    /// Like `reset`, but the display is left as it was,
+    /// to look at what was drawn before the machine was reset.
+    pub fn reset_keep_display(&mut self) {
+        self.rng = ChaCha12Rng::seed_from_u64(self.seed);
+        if self.quirks.randomize_memory {
+            self.rng.fill(&mut self.mem[..]);
+        } else {
+            self.mem = [0; MEM_SIZE];
+        }
         self.mem[FONT_OFFSET..FONT_OFFSET + FONT_SPRITES.len()].copy_from_slice(&FONT_SPRITES);
-        self.fb = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
         self.v = [0; 0x10];
         self.i = 0;
         self.dt = 0;
         self.st = 0;
-        self.pc = 0x200;
+        self.pc = self.entry_point;
         self.sp = 0;
         self.stack = [0; 16];
         self.keypad = [false; 16];
+        self.frame_keypad = [false; 16];
+        self.prev_keypad = [false; 16];
+        self.key_presses = 0;
+        self.key_order = [0; 16];
+        self.frame_collision = false;
+        self.frame_drew = false;
+        self.frame_fb_changed = false;
+        self.history.clear();
+        self.rewind.clear();
+        self.stopped_at = None;
+        self.warned.clear();
+        self.frames = 0;
+        self.instructions = 0;
+    }
+
+    /// Sets the address where execution begins, on this and every following reset.
+    pub fn set_entry_point(&mut self, addr: u16) -> Result<(), ChipError> {
+        if addr as usize >= MEM_SIZE - 1 {
+            return Err(ChipError::PcOutOfBounds(addr));
+        }
+        self.entry_point = addr;
+        self.pc = addr;
+        Ok(())
+    }
+
+    /// Returns the active quirks.
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Sets the quirks used by the following instructions.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Returns how many times per second the timers decrement.
+    pub fn timer_hz(&self) -> u32 {
+        self.timer_hz
+    }
+
+    /// Sets how many times per second the timers decrement,
+    /// like 50 for PAL machines.
+    ///
+    /// The timers decrement once per frame, so this only changes
+    /// `frame_period`, and frontends should run a frame every `frame_period`.
+    pub fn set_timer_hz(&mut self, hz: u32) -> Result<(), ChipError> {
+        if hz == 0 {
+            return Err(ChipError::ZeroTimerRate);
+        }
+        self.timer_hz = hz;
+        Ok(())
+    }
+
+    /// Returns the time between two frames at the current timer rate.
+    pub fn frame_period(&self) -> Duration {
+        Duration::from_secs(1) / self.timer_hz
+    }
+
+    /// Sets when `frame` decrements the timers, at its start by default.
+    pub fn set_timer_tick_position(&mut self, position: TimerTickPosition) {
+        self.timer_tick_position = position;
+    }
+
+    /// Returns when `frame` decrements the timers.
+    pub fn timer_tick_position(&self) -> TimerTickPosition {
+        self.timer_tick_position
+    }
+
+    /// Sets the handler called for `0NNN` (call machine code routine at `NNN`).
+    ///
+    /// Without a handler, `0NNN` is an unrecognized opcode.
+    pub fn set_machine_routine_handler(&mut self, handler: Box<MachineRoutine>) {
+        self.machine_routine = Hook(Some(handler));
+    }
+
+    /// Sets a callback called at the end of each frame in which
+    /// the frame buffer was modified.
+    pub fn set_render_hook(&mut self, hook: Box<RenderHook>) {
+        self.render_hook = Hook(Some(hook));
+    }
+
+    /// Sets a callback called before each jump or call to an odd address.
+    ///
+    /// Odd targets are legal, but they usually come from a bug,
+    /// as every following instruction is fetched misaligned.
+    pub fn set_odd_jump_hook(&mut self, hook: Box<OddJumpHook>) {
+        self.odd_jump_hook = Hook(Some(hook));
     }
 
     /// Returns true if the buzzer is on.
@@ -85,6 +435,10 @@ impl Chip8 {
 
     /// Sets key `k` as pressed.
     pub fn key_down(&mut self, k: usize) {
+        if !self.keypad[k] {
+            self.key_presses += 1;
+            self.key_order[k] = self.key_presses;
+        }
         self.keypad[k] = true;
     }
 
@@ -93,59 +447,421 @@ impl Chip8 {
         self.keypad[k] = false;
     }
 
-    /// Returns the frame buffer.
-    pub fn fb(&self) -> &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT] {
+    /// Returns the rows of the frame buffer.
+    /// Their size depends on the active resolution, see `dimensions`.
+    pub fn fb(&self) -> ChunksExact<'_, bool> {
+        self.fb.chunks_exact(self.dimensions().0)
+    }
+
+    /// Returns the frame buffer as a single slice, row by row.
+    pub fn fb_flat(&self) -> &[bool] {
         &self.fb
     }
 
-    /// Loads the given rom in memory.
-    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), ChipError> {
-        if rom.len() > 0xe00 {
+    /// Returns the width of the screen in the low resolution,
+    /// the size frontends usually open their window at.
+    pub const fn screen_width() -> usize {
+        SCREEN_WIDTH
+    }
+
+    /// Returns the height of the screen in the low resolution.
+    pub const fn screen_height() -> usize {
+        SCREEN_HEIGHT
+    }
+
+    /// Returns the width and height of the screen in the active resolution.
+    pub fn dimensions(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    /// Enables the screen persistence model, where a pixel turned off
+    /// keeps glowing for `frames` frames, fading a little each frame.
+    /// 0 disables it.
+    ///
+    /// This smooths the flicker of roms that erase and redraw their sprites.
+    pub fn set_persistence(&mut self, frames: u8) {
+        self.persistence_frames = frames;
+        self.persistence = if frames > 0 {
+            self.fb.iter().map(|&p| if p { 0xff } else { 0 }).collect()
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Returns the brightness of each pixel, row by row like `fb_flat`,
+    /// or `None` if screen persistence is disabled.
+    ///
+    /// Lit pixels are at 255, pixels turned off fade to 0.
+    pub fn persistence(&self) -> Option<&[u8]> {
+        if self.persistence_frames > 0 {
+            Some(&self.persistence)
+        } else {
+            None
+        }
+    }
+
+    /// Fades the pixels that are off, at the end of a frame.
+    fn decay_persistence(&mut self) {
+        if self.persistence_frames == 0 {
+            return;
+        }
+        // the resolution may have changed
+        self.persistence.resize(self.fb.len(), 0);
+        let frames = self.persistence_frames as u16 + 1;
+        let decay = 0xff_u16.div_ceil(frames) as u8;
+        for (brightness, &p) in self.persistence.iter_mut().zip(&self.fb) {
+            *brightness = if p {
+                0xff
+            } else {
+                brightness.saturating_sub(decay)
+            };
+        }
+    }
+
+    /// Returns an empty frame buffer for the active resolution.
+    fn blank_fb(&self) -> Vec<bool> {
+        let (width, height) = self.dimensions();
+        vec![false; width * height]
+    }
+
+    /// Returns the index of a pixel in the frame buffer.
+    fn pixel_index(&self, x: usize, y: usize) -> usize {
+        y * self.dimensions().0 + x
+    }
+
+    /// Returns the frame buffer as tightly packed RGBA8 pixels, row by row,
+    /// using `fg` for lit pixels and `bg` for the others.
+    pub fn fb_to_rgba(&self, fg: [u8; 4], bg: [u8; 4]) -> Vec<u8> {
+        self.fb
+            .iter()
+            .flat_map(|&p| if p { fg } else { bg })
+            .collect()
+    }
+
+    /// Returns a hash of the frame buffer, stable across runs and platforms,
+    /// to compare screens against recorded values.
+    pub fn fb_hash(&self) -> u64 {
+        // 64 bit FNV-1a over one byte per pixel
+        self.fb.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &p| {
+            (hash ^ p as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Loads the given rom in memory at `0x200`.
+    ///
+    /// The program counter and the entry point are left as they are,
+    /// so execution begins wherever `set_entry_point` put it.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<LoadInfo, ChipError> {
+        if rom.len() > MAX_ROM_SIZE {
             return Err(ChipError::RomTooBig(rom.len()));
         }
-        self.mem[0x200..0x200 + rom.len()].copy_from_slice(rom);
+        self.copy_rom(rom, PROGRAM_OFFSET as u16)
+    }
+
+    /// Loads the given rom in memory at `base`, like the ETI 660 roms
+    /// at `0x600`, and moves the program counter and the entry point there.
+    ///
+    /// `base` can't be below `0x200`, so the font and the interpreter area are kept.
+    pub fn load_rom_at(&mut self, rom: &[u8], base: u16) -> Result<LoadInfo, ChipError> {
+        let info = self.copy_rom(rom, base)?;
+        self.entry_point = base;
+        self.pc = base;
+        Ok(info)
+    }
+
+    /// Copies the rom in memory at `base`, to be loaded again by `reload`.
+    fn copy_rom(&mut self, rom: &[u8], base: u16) -> Result<LoadInfo, ChipError> {
+        let start = base as usize;
+        if start < PROGRAM_OFFSET {
+            return Err(ChipError::LoadAddrTooLow(base));
+        }
+        if start >= MEM_SIZE - 1 || rom.len() > MEM_SIZE - start {
+            return Err(ChipError::RomDoesNotFit(base, rom.len()));
+        }
+        self.mem[start..start + rom.len()].copy_from_slice(rom);
+        self.rom = rom.to_vec();
+        self.rom_base = base;
+        Ok(LoadInfo {
+            bytes_loaded: rom.len(),
+            free_bytes: MEM_SIZE - start - rom.len(),
+            load_address: base,
+        })
+    }
+
+    /// Resets the machine and loads the last rom loaded with `load_rom` again.
+    pub fn reload(&mut self) {
+        self.reset();
+        let start = self.rom_base as usize;
+        self.mem[start..start + self.rom.len()].copy_from_slice(&self.rom);
+    }
+
+    /// Loads a full memory image, like the dumps of other emulators,
+    /// and moves the program counter to `0x200`.
+    ///
+    /// Unlike `load_rom`, the font and everything below `0x200` is replaced.
+    pub fn load_memory_dump(&mut self, dump: &[u8]) -> Result<(), ChipError> {
+        if dump.len() != MEM_SIZE {
+            return Err(ChipError::BadDumpSize(dump.len()));
+        }
+        self.mem.copy_from_slice(dump);
+        self.pc = PROGRAM_OFFSET as u16;
         Ok(())
     }
 
+    /// Returns the keypad as seen by the instructions.
+    fn input(&self) -> &[bool; 16] {
+        if self.quirks.latch_keypad_per_frame {
+            &self.frame_keypad
+        } else {
+            &self.keypad
+        }
+    }
+
     fn nnn(&self) -> u16 {
         (self.mem[self.pc as usize] as u16 & 0x0f) << 8 | self.mem[self.pc as usize + 1] as u16
     }
 
     /// Advances the emulation up until the next frame.
-    /// Each frame executes `n` instructions.
-    pub fn frame(&mut self, n: usize) -> Result<(), ChipError> {
+    /// Each frame executes `n` instructions and decrements the timers once,
+    /// unless they are ticked manually, see `TimerTickPosition`.
+    ///
+    /// Returns how many instructions ran, which is less than `n`
+    /// when the frame ends early on a draw.
+    pub fn frame(&mut self, n: usize) -> Result<usize, ChipError> {
+        self.run_frame(n, None).map(|(_, steps)| steps)
+    }
+
+    /// Like `frame`, but stops early if the deadline passes,
+    /// to keep untrusted roms with a high `n` from hanging the host.
+    pub fn frame_with_deadline(
+        &mut self,
+        n: usize,
+        deadline: Option<Instant>,
+    ) -> Result<RunStatus, ChipError> {
+        self.run_frame(n, deadline).map(|(status, _)| status)
+    }
+
+    /// Runs a whole frame of `ipf` instructions with the keys pressed
+    /// in `keys`, where bit `k` is set if key `k` is pressed.
+    ///
+    /// This is all most frontends need to call once per frame.
+    pub fn tick(&mut self, ipf: usize, keys: u16) -> Result<TickResult, ChipError> {
+        for k in 0..16 {
+            if keys & (1 << k) != 0 {
+                self.key_down(k);
+            } else {
+                self.key_up(k);
+            }
+        }
+        self.frame(ipf)?;
+        Ok(TickResult {
+            framebuffer_changed: self.frame_fb_changed,
+            buzzer_on: self.buzzer(),
+            drew: self.frame_drew,
+        })
+    }
+
+    /// Runs a frame, returning how it ended and how many instructions ran.
+    fn run_frame(
+        &mut self,
+        n: usize,
+        deadline: Option<Instant>,
+    ) -> Result<(RunStatus, usize), ChipError> {
+        self.frames += 1;
+        self.frame_collision = false;
+        self.frame_drew = false;
+        self.frame_keypad = self.keypad;
+        if self.timer_tick_position == TimerTickPosition::FrameStart {
+            self.tick_timers();
+        }
+        let mut status = RunStatus::Completed;
+        let mut steps = 0;
+        while steps < n {
+            if clock::passed(deadline) {
+                status = RunStatus::DeadlineExceeded;
+                break;
+            }
+            let last_op = self.step()?;
+            steps += 1;
+            if last_op & 0xf000 == 0xd000 {
+                self.frame_drew = true;
+                break;
+            }
+        }
+        if self.timer_tick_position == TimerTickPosition::FrameEnd {
+            self.tick_timers();
+        }
+        self.apply_cheats();
+        self.decay_persistence();
+
+        self.frame_fb_changed = self.fb_dirty;
+        if self.fb_dirty {
+            self.fb_dirty = false;
+            let dimensions = self.dimensions();
+            if let Some(hook) = self.render_hook.0.as_mut() {
+                hook(&self.fb, dimensions);
+            }
+        }
+        Ok((status, steps))
+    }
+
+    /// Decrements the timers that are not 0 yet.
+    ///
+    /// `frame` already does it, unless the timer tick position is `Manual`,
+    /// in which case this should be called once every `frame_period`.
+    pub fn tick_timers(&mut self) {
         if self.dt > 0 {
             self.dt -= 1;
         }
         if self.st > 0 {
             self.st -= 1;
         }
-        for _ in 0..n {
-            //self.step();
-            let last_op = self.step()?;
-            if last_op & 0xf000 == 0xd000 {
-                break;
+    }
+
+    /// Executes up to `steps` instructions, without frames or timers,
+    /// stopping early if the deadline passes, or on a jump to itself
+    /// if enabled with `set_stop_on_self_jump`.
+    pub fn run(&mut self, steps: usize, deadline: Option<Instant>) -> Result<RunStatus, ChipError> {
+        for _ in 0..steps {
+            if clock::passed(deadline) {
+                return Ok(RunStatus::DeadlineExceeded);
+            }
+            if self.stop_on_self_jump && self.at_self_jump() {
+                return Ok(RunStatus::Halted);
             }
+            self.step()?;
+        }
+        Ok(RunStatus::Completed)
+    }
+
+    /// Makes `run` stop with `RunStatus::Halted` when reaching a jump to itself,
+    /// instead of running it until the end of the budget.
+    pub fn set_stop_on_self_jump(&mut self, stop: bool) {
+        self.stop_on_self_jump = stop;
+    }
+
+    /// Returns true if the next instruction is a jump to itself,
+    /// which test roms use to stop once done.
+    pub fn at_self_jump(&self) -> bool {
+        let pc = self.pc as usize;
+        pc < MEM_SIZE - 1 && self.mem[pc] & 0xf0 == 0x10 && self.nnn() == self.pc
+    }
+
+    /// Runs `frames` frames of `ipf` instructions, as fast as possible,
+    /// to check the final screen with `fb_hash` without a frontend.
+    pub fn run_frames(&mut self, frames: usize, ipf: usize) -> Result<(), ChipError> {
+        for _ in 0..frames {
+            self.frame(ipf)?;
         }
         Ok(())
     }
 
+    /// Runs `frames` frames of `ipf` instructions, returning the hash
+    /// of the frame buffer after each one.
+    ///
+    /// Recording the hashes once gives golden values for regression tests.
+    pub fn run_frames_collecting(
+        &mut self,
+        frames: usize,
+        ipf: usize,
+    ) -> Result<Vec<u64>, ChipError> {
+        let mut hashes = Vec::with_capacity(frames);
+        for _ in 0..frames {
+            self.frame(ipf)?;
+            hashes.push(self.fb_hash());
+        }
+        Ok(hashes)
+    }
+
+    /// Returns how many frames ran since the last reset.
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns how many instructions were executed since the last reset.
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Returns true if the last frame ran a draw instruction,
+    /// so frontends can skip redrawing the screen otherwise.
+    pub fn drew_last_frame(&self) -> bool {
+        self.frame_drew
+    }
+
     /// Reads and executes the next operation.
     pub fn step(&mut self) -> Result<u16, ChipError> {
-        if self.pc as usize > MEM_SIZE {
+        let res = self.fetch_and_execute();
+        if let Err(e) = &res {
+            if self.crash_reports {
+                self.crash_report = Some(self.make_crash_report(e));
+            }
+        }
+        res
+    }
+
+    fn fetch_and_execute(&mut self) -> Result<u16, ChipError> {
+        if self.pc as usize >= MEM_SIZE - 1 {
             return Err(ChipError::PcOutOfBounds(self.pc));
         }
         let hi_op = self.mem[self.pc as usize];
         let lo_op = self.mem[self.pc as usize + 1];
         let op = ((hi_op as u16) << 8) | (lo_op as u16);
 
+        if self.history_depth > 0 {
+            if self.history.len() >= self.history_depth {
+                self.history.pop_front();
+            }
+            self.history.push_back(HistoryEntry {
+                pc: self.pc,
+                op,
+                v: self.v,
+                i: self.i,
+                sp: self.sp,
+                dt: self.dt,
+                st: self.st,
+            });
+        }
+        if self.record_rewind && self.rewind_depth > 0 {
+            if self.rewind.len() >= self.rewind_depth {
+                self.rewind.pop_front();
+            }
+            self.rewind.push_back(self.snapshot());
+        }
+
+        if self.schip_warnings {
+            self.warn_schip(op);
+        }
+        match self.dispatch {
+            Dispatch::Match => self.execute(op)?,
+            Dispatch::Table => self.execute_table(op)?,
+        }
+        // for FX0A to see the keys released by the next instruction
+        self.prev_keypad = *self.input();
+
+        self.pc = self.pc.wrapping_add(2);
+        self.instructions += 1;
+        self.stopped_at = None;
+        Ok(op)
+    }
+
+    /// Executes an opcode, without moving `pc` past it.
+    fn execute(&mut self, op: u16) -> Result<(), ChipError> {
+        let [hi_op, lo_op] = op.to_be_bytes();
         match hi_op & 0xf0 {
-            0x00 => match lo_op {
-                0xe0 => self.opcode_cls(),
-                0xee => self.opcode_ret(),
-                _ => return Err(ChipError::UnrecognizedOpcode(op)),
+            0x00 => match op {
+                0x00e0 => self.opcode_cls(),
+                0x00ee => self.opcode_ret()?,
+                0x00fe => self.opcode_low(),
+                0x00ff => self.opcode_high(),
+                0x0000 if self.quirks.treat_zero_as_nop => {}
+                _ => self.opcode_sys(self.nnn())?,
             },
-            0x10 => self.opcode_jp(self.nnn()),
+            0x10 => self.opcode_jp(self.nnn())?,
             0x20 => self.opcode_call(self.nnn())?,
             0x30 => {
                 let x = lo_nib(hi_op) as usize;
@@ -155,7 +871,7 @@ impl Chip8 {
                 let x = lo_nib(hi_op) as usize;
                 self.opcode_sne(x, lo_op);
             }
-            0x50 => {
+            0x50 if lo_nib(lo_op) == 0 => {
                 let x = lo_nib(hi_op) as usize;
                 let y = hi_nib(lo_op) as usize;
                 self.opcode_se_r(x, y);
@@ -184,13 +900,13 @@ impl Chip8 {
                     _ => return Err(ChipError::UnrecognizedOpcode(op)),
                 }
             }
-            0x90 => {
+            0x90 if lo_nib(lo_op) == 0 => {
                 let x = lo_nib(hi_op) as usize;
                 let y = hi_nib(lo_op) as usize;
                 self.opcode_sne_r(x, y);
             }
             0xa0 => self.opcode_ld_i(self.nnn()),
-            0xb0 => self.opcode_jp_r(self.nnn()),
+            0xb0 => self.opcode_jp_r(self.nnn())?,
             0xc0 => {
                 let x = lo_nib(hi_op) as usize;
                 self.opcode_rnd(x, lo_op);
@@ -199,7 +915,7 @@ impl Chip8 {
                 let x = lo_nib(hi_op) as usize;
                 let y = hi_nib(lo_op) as usize;
                 let n = lo_nib(lo_op) as usize;
-                self.opcode_drw(x, y, n);
+                self.opcode_drw(x, y, n)?;
             }
             0xe0 => match lo_op {
                 0x9e => self.opcode_skp(lo_nib(hi_op) as usize),
@@ -215,41 +931,89 @@ impl Chip8 {
                     0x18 => self.opcode_ld_st(x),
                     0x1e => self.opcode_add_i(x),
                     0x29 => self.opcode_ld_digit(x),
-                    0x33 => self.opcode_ld_bcd(x),
-                    0x55 => self.opcode_ld_mass_store(x),
-                    0x65 => self.opcode_ld_mass_load(x),
+                    0x33 => self.opcode_ld_bcd(x)?,
+                    0x55 => self.opcode_ld_mass_store(x)?,
+                    0x65 => self.opcode_ld_mass_load(x)?,
                     _ => return Err(ChipError::UnrecognizedOpcode(op)),
                 }
             }
             _ => return Err(ChipError::UnrecognizedOpcode(op)),
         }
+        Ok(())
+    }
 
-        self.pc += 2;
-        Ok(op)
+    fn opcode_sys(&mut self, addr: u16) -> Result<(), ChipError> {
+        let mut handler = match self.machine_routine.0.take() {
+            Some(handler) => handler,
+            None => return Err(ChipError::UnrecognizedOpcode(addr)),
+        };
+        handler(self, addr);
+        // the handler may have installed a new one
+        if self.machine_routine.0.is_none() {
+            self.machine_routine.0 = Some(handler);
+        }
+        Ok(())
     }
 
     fn opcode_cls(&mut self) {
-        self.fb = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        self.fb.fill(false);
+        self.fb_dirty = true;
     }
 
-    fn opcode_ret(&mut self) {
-        self.pc = self.stack[self.sp];
+    fn opcode_low(&mut self) {
+        self.set_resolution(false);
+    }
+
+    fn opcode_high(&mut self) {
+        self.set_resolution(true);
+    }
+
+    fn opcode_ret(&mut self) -> Result<(), ChipError> {
+        if self.sp == 0 {
+            return Err(ChipError::SpOutOfBounds(self.sp));
+        }
         self.sp -= 1;
+        self.pc = self.stack[self.sp as usize];
+        Ok(())
     }
 
-    fn opcode_jp(&mut self, addr: u16) {
-        self.pc = addr;
-        self.pc -= 2;
+    /// Checks a jump target when `validate_jump_targets` is enabled,
+    /// rejecting the targets the next fetch would fail on,
+    /// and reports odd targets to the odd jump hook.
+    fn check_jump(&mut self, addr: u16) -> Result<(), ChipError> {
+        if self.quirks.validate_jump_targets && addr as usize >= MEM_SIZE - 1 {
+            return Err(ChipError::PcOutOfBounds(addr));
+        }
+        if addr % 2 == 1 {
+            if let Some(hook) = self.odd_jump_hook.0.as_mut() {
+                hook(self.pc, addr);
+            }
+        }
+        Ok(())
+    }
+
+    fn opcode_jp(&mut self, addr: u16) -> Result<(), ChipError> {
+        self.check_jump(addr)?;
+        self.pc = addr.wrapping_sub(2);
+        Ok(())
     }
 
     fn opcode_call(&mut self, addr: u16) -> Result<(), ChipError> {
-        if self.sp >= 15 {
-            return Err(ChipError::SpOutOfBounds(self.sp));
+        self.check_jump(addr)?;
+        if self.sp as usize >= STACK_DEPTH {
+            match self.quirks.stack_overflow {
+                StackOverflowPolicy::Error => return Err(ChipError::SpOutOfBounds(self.sp)),
+                StackOverflowPolicy::Wrap => {
+                    self.stack[0] = self.pc;
+                    self.sp = 1;
+                }
+                StackOverflowPolicy::Ignore => {}
+            }
+        } else {
+            self.stack[self.sp as usize] = self.pc;
+            self.sp += 1;
         }
-        self.sp += 1;
-        self.stack[self.sp] = self.pc;
-        self.pc = addr;
-        self.pc -= 2;
+        self.pc = addr.wrapping_sub(2);
         Ok(())
     }
 
@@ -285,44 +1049,67 @@ impl Chip8 {
 
     fn opcode_or(&mut self, x: usize, y: usize) {
         self.v[x] |= self.v[y];
+        if self.quirks.logic_resets_vf {
+            self.v[VF] = 0;
+        }
     }
 
     fn opcode_and(&mut self, x: usize, y: usize) {
         self.v[x] &= self.v[y];
+        if self.quirks.logic_resets_vf {
+            self.v[VF] = 0;
+        }
     }
 
     fn opcode_xor(&mut self, x: usize, y: usize) {
         self.v[x] ^= self.v[y];
+        if self.quirks.logic_resets_vf {
+            self.v[VF] = 0;
+        }
+    }
+
+    /// Writes the result of an arithmetic instruction in `Vx` and its flag in `VF`.
+    /// The flag is written last, unless the quirk says otherwise,
+    /// so with `VF` as destination only the flag is left.
+    fn set_with_flag(&mut self, x: usize, res: u8, flag: bool) {
+        if self.quirks.flag_before_result {
+            self.v[VF] = flag as u8;
+            self.v[x] = res;
+        } else {
+            self.v[x] = res;
+            self.v[VF] = flag as u8;
+        }
     }
 
     fn opcode_add_r(&mut self, x: usize, y: usize) {
         let (res, overflow) = self.v[x].overflowing_add(self.v[y]);
-        self.v[0xf] = if overflow { 1 } else { 0 };
-        self.v[x] = res;
+        self.set_with_flag(x, res, overflow);
     }
 
     fn opcode_sub(&mut self, x: usize, y: usize) {
         let (res, overflow) = self.v[x].overflowing_sub(self.v[y]);
-        self.v[0xf] = if overflow { 0 } else { 1 }; // NOT borrow
-        self.v[x] = res;
+        self.set_with_flag(x, res, !overflow); // NOT borrow
     }
 
-    fn opcode_shr(&mut self, x: usize, _y: usize) {
-        // for now y is unused
-        self.v[0xf] = self.v[x] & 1;
-        self.v[x] >>= 1;
+    fn opcode_shr(&mut self, x: usize, y: usize) {
+        if self.quirks.shift_uses_vy {
+            self.v[x] = self.v[y];
+        }
+        let flag = self.v[x] & 1 == 1;
+        self.set_with_flag(x, self.v[x] >> 1, flag);
     }
 
     fn opcode_subn(&mut self, x: usize, y: usize) {
         let (res, overflow) = self.v[y].overflowing_sub(self.v[x]);
-        self.v[0xf] = if overflow { 0 } else { 1 }; // NOT borrow
-        self.v[x] = res;
+        self.set_with_flag(x, res, !overflow); // NOT borrow
     }
 
-    fn opcode_shl(&mut self, x: usize, _y: usize) {
-        // for now y is unused
-        self.v[0xf] = (self.v[x] >> 7) & 1;
-        self.v[x] <<= 1;
+    fn opcode_shl(&mut self, x: usize, y: usize) {
+        if self.quirks.shift_uses_vy {
+            self.v[x] = self.v[y];
+        }
+        let flag = self.v[x] >> 7 == 1;
+        self.set_with_flag(x, self.v[x] << 1, flag);
     }
 
     fn opcode_sne_r(&mut self, x: usize, y: usize) {
@@ -335,47 +1122,172 @@ impl Chip8 {
         self.i = addr;
     }
 
-    fn opcode_jp_r(&mut self, addr: u16) {
-        self.pc = addr + (self.v[0] as u16);
+    /// Past the end of memory, the target wraps around,
+    /// unless jump targets are validated.
+    fn opcode_jp_r(&mut self, addr: u16) -> Result<(), ChipError> {
+        let x = if self.quirks.jump_with_vx {
+            (addr >> 8) as usize
+        } else {
+            0
+        };
+        let target = addr + (self.v[x] as u16);
+        self.check_jump(target)?;
+        self.pc = (target % MEM_SIZE as u16).wrapping_sub(2);
+        Ok(())
     }
 
     fn opcode_rnd(&mut self, x: usize, byte: u8) {
-        self.v[x] = thread_rng().gen_range(0..=0xff) & byte;
+        self.v[x] = self.rng.gen_range(0..=0xff) & byte;
+    }
+
+    /// XORs a sprite on the frame buffer, with its top left corner at (`x`, `y`).
+    /// Pixels falling outside the screen are clipped.
+    /// Returns true if any pixel was turned off.
+    fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.draw_sprite_rows(x, y, sprite, 1)
     }
 
-    fn opcode_drw(&mut self, x: usize, y: usize, n: usize) {
-        let bytes = &self.mem[(self.i as usize)..(self.i as usize) + n];
-        self.v[0xf] = 0;
-        let x = (self.v[x] as usize) % SCREEN_WIDTH;
-        let y = (self.v[y] as usize) % SCREEN_HEIGHT;
+    /// Like `draw_sprite`, for sprites `row_bytes` bytes wide,
+    /// like the 16x16 SUPER-CHIP sprites.
+    fn draw_sprite_rows(&mut self, x: usize, y: usize, sprite: &[u8], row_bytes: usize) -> bool {
+        let (width, height) = self.dimensions();
+        let mut collision = false;
 
-        for (j, byte) in bytes.iter().enumerate() {
+        for (j, row) in sprite.chunks(row_bytes).enumerate() {
             let p_y = y + j;
-            if p_y >= SCREEN_HEIGHT {
+            if p_y >= height {
                 break;
             }
-            for i in 0..8 {
+            for i in 0..8 * row.len() {
                 let p_x = x + i;
-                if p_x >= SCREEN_WIDTH {
+                if p_x >= width {
                     break;
                 }
-                let p_mask = ((byte >> (7 - i)) & 1) == 1;
-                if self.fb[p_y][p_x] && p_mask {
-                    self.v[0xf] = 1;
+                let p_mask = ((row[i / 8] >> (7 - i % 8)) & 1) == 1;
+                let p = self.pixel_index(p_x, p_y);
+                if self.fb[p] && p_mask {
+                    collision = true;
+                }
+                self.fb[p] ^= p_mask;
+                self.fb_dirty |= p_mask;
+                if self.fb[p] {
+                    // re-lit, even if erased again before the frame ends
+                    if let Some(brightness) = self.persistence.get_mut(p) {
+                        *brightness = 0xff;
+                    }
                 }
-                self.fb[p_y][p_x] ^= p_mask;
             }
         }
+
+        collision
+    }
+
+    /// Warns the first time each SUPER-CHIP instruction
+    /// the platform doesn't support is about to run.
+    fn warn_schip(&mut self, op: u16) {
+        let ins = match decode(op) {
+            Some(ins) if ins.is_schip() && !self.platform.supports(ins) => ins,
+            _ => return,
+        };
+        if !self.warned.contains(&discriminant(&ins)) {
+            self.warned.push(discriminant(&ins));
+            self.warnings.push(format!(
+                "the SUPER-CHIP instruction {} ({:#06X}) at {:#05X} \
+                 is not supported on {:?}",
+                disassemble(op),
+                op,
+                self.pc,
+                self.platform
+            ));
+        }
+    }
+
+    /// Writes a byte of memory, recording it if the write log is enabled.
+    fn write_mem(&mut self, addr: usize, val: u8) {
+        self.write_mem_from(addr, val, Some(self.pc));
+    }
+
+    /// Writes a byte of memory, recording it as written by the instruction
+    /// at `pc` if the write log is enabled.
+    fn write_mem_from(&mut self, addr: usize, val: u8, pc: Option<u16>) {
+        if let Some(log) = self.memory_writes.as_mut() {
+            log.push(MemoryWrite {
+                addr: addr as u16,
+                old: self.mem[addr],
+                new: val,
+                pc,
+            });
+        }
+        self.mem[addr] = val;
+    }
+
+    /// Returns the `len` bytes of memory starting at `I`,
+    /// or an error if they don't fit in memory.
+    fn i_range(&self, len: usize) -> Result<Range<usize>, ChipError> {
+        let i = self.i as usize;
+        if i + len > MEM_SIZE {
+            return Err(ChipError::AddrOutOfBounds(self.i));
+        }
+        Ok(i..i + len)
+    }
+
+    /// Copies the memory in `range` to `buf`, reading 0 below the font
+    /// if the quirk says so.
+    fn read_mem(&self, range: Range<usize>, buf: &mut [u8]) {
+        let start = range.start;
+        buf.copy_from_slice(&self.mem[range]);
+        if self.quirks.zero_below_font && start < FONT_OFFSET {
+            let n = (FONT_OFFSET - start).min(buf.len());
+            buf[..n].fill(0);
+        }
+    }
+
+    /// The origin of the sprite wraps around the screen, unless a quirk
+    /// says otherwise, while the sprite itself is clipped at the edges.
+    ///
+    /// In the high resolution, `DXY0` draws a 16x16 sprite of 32 bytes.
+    fn opcode_drw(&mut self, x: usize, y: usize, n: usize) -> Result<(), ChipError> {
+        let big = n == 0 && self.hires;
+        let len = if big { 32 } else { n };
+        let mut sprite = [0; 32];
+        self.read_mem(self.i_range(len)?, &mut sprite[..len]);
+        let (width, height) = self.dimensions();
+        let wrap = !self.quirks.clip_origin;
+        let x = if wrap {
+            (self.v[x] as usize) % width
+        } else {
+            self.v[x] as usize
+        };
+        let y = if self.quirks.clamp_origin_y {
+            (self.v[y] as usize).min(height - 1)
+        } else if wrap {
+            (self.v[y] as usize) % height
+        } else {
+            self.v[y] as usize
+        };
+
+        let mut collision = if big {
+            self.draw_sprite_rows(x, y, &sprite, 2)
+        } else {
+            self.draw_sprite(x, y, &sprite[..n])
+        };
+
+        if self.quirks.latch_collision_per_frame {
+            self.frame_collision |= collision;
+            collision = self.frame_collision;
+        }
+        self.v[VF] = collision as u8;
+        Ok(())
     }
 
     fn opcode_skp(&mut self, x: usize) {
-        if self.keypad[self.v[x] as usize] {
+        if self.input()[lo_nib(self.v[x]) as usize] {
             self.pc += 2;
         }
     }
 
     fn opcode_sknp(&mut self, x: usize) {
-        if !self.keypad[self.v[x] as usize] {
+        if !self.input()[lo_nib(self.v[x]) as usize] {
             self.pc += 2;
         }
     }
@@ -384,19 +1296,25 @@ impl Chip8 {
         self.v[x] = self.dt;
     }
 
+    /// Waits for a key to be released since the previous instruction,
+    /// or to be down with the `ld_k_on_press` quirk.
     fn opcode_ld_k(&mut self, x: usize) {
-        if self.keypad.iter().all(|&e| !e) {
-            self.pc -= 2;
-        } else {
-            let press = self
-                .keypad
-                .iter()
-                .enumerate()
-                .filter(|(_, &p)| p)
-                .map(|(i, _)| i)
-                .next()
-                .unwrap();
-            self.v[x] = press as u8;
+        let input = *self.input();
+        let mut keys = (0..16).filter(|&k| {
+            if self.quirks.ld_k_on_press {
+                input[k]
+            } else {
+                self.prev_keypad[k] && !input[k]
+            }
+        });
+        let key = match self.quirks.ld_k_policy {
+            LdKPolicy::LowestIndex => keys.next(),
+            LdKPolicy::MostRecent => keys.max_by_key(|&k| self.key_order[k]),
+        };
+        match key {
+            Some(key) => self.v[x] = key as u8,
+            // step moves pc past the instruction, so this runs it again
+            None => self.pc = self.pc.wrapping_sub(2),
         }
     }
 
@@ -409,38 +1327,49 @@ impl Chip8 {
     }
 
     fn opcode_add_i(&mut self, x: usize) {
-        self.i += self.v[x] as u16;
+        self.i = self.i.wrapping_add(self.v[x] as u16);
     }
 
     fn opcode_ld_digit(&mut self, x: usize) {
         self.i = FONT_OFFSET as u16 + 5 * self.v[x] as u16;
     }
 
-    fn opcode_ld_bcd(&mut self, x: usize) {
-        let i = self.i as usize;
-        self.mem[i] = self.v[x] / 100;
-        self.mem[i + 1] = (self.v[x] % 100) / 10;
-        self.mem[i + 2] = self.v[x] % 10;
+    fn opcode_ld_bcd(&mut self, x: usize) -> Result<(), ChipError> {
+        let i = self.i_range(3)?.start;
+        self.write_mem(i, self.v[x] / 100);
+        self.write_mem(i + 1, (self.v[x] % 100) / 10);
+        self.write_mem(i + 2, self.v[x] % 10);
+        Ok(())
     }
 
-    fn opcode_ld_mass_store(&mut self, x: usize) {
-        let i = self.i as usize;
-        for r in 0..=x {
-            self.mem[i + r] = self.v[r];
+    fn opcode_ld_mass_store(&mut self, x: usize) -> Result<(), ChipError> {
+        let range = self.i_range(x + 1)?;
+        for (addr, r) in range.zip(0..=x) {
+            self.write_mem(addr, self.v[r]);
+        }
+        if self.quirks.mass_load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
         }
+        Ok(())
     }
 
-    fn opcode_ld_mass_load(&mut self, x: usize) {
-        let i = self.i as usize;
-        for r in 0..=x {
-            self.v[r] = self.mem[i + r];
+    fn opcode_ld_mass_load(&mut self, x: usize) -> Result<(), ChipError> {
+        let mut regs = [0; 0x10];
+        self.read_mem(self.i_range(x + 1)?, &mut regs[..=x]);
+        self.v[..=x].copy_from_slice(&regs[..=x]);
+        if self.quirks.mass_load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     fn chip_with_rom(rom: &[u8]) -> Chip8 {
         let mut chip = Chip8::new();
@@ -448,6 +1377,726 @@ mod tests {
         chip
     }
 
+    #[test]
+    fn randomized_memory() {
+        let quirks = Quirks {
+            randomize_memory: true,
+            ..Quirks::default()
+        };
+        let mut chip = Chip8::with_seed(13);
+        chip.set_quirks(quirks);
+        chip.reset();
+        let mut other = Chip8::with_seed(13);
+        other.set_quirks(quirks);
+        other.reset();
+
+        assert_eq!(chip.mem, other.mem);
+        assert!(chip.mem[0x200..].iter().any(|&b| b != 0));
+        assert_eq!(
+            chip.mem[FONT_OFFSET..FONT_OFFSET + FONT_SPRITES.len()],
+            FONT_SPRITES
+        );
+
+        chip.load_rom(&[0x12, 0x34]).expect("error loading rom");
+        assert_eq!(chip.mem[0x200..0x202], [0x12, 0x34]);
+    }
+
+    #[test]
+    fn seeded_random() {
+        // RND V0..VF with masks 0xFF, then JP 0x200
+        let mut rom: Vec<u8> = (0..16).flat_map(|x| [0xc0 | x, 0xff]).collect();
+        rom.extend([0x12, 0x00]);
+
+        let run = |chip: &mut Chip8| {
+            (0..8)
+                .map(|_| {
+                    chip.frame(17).expect("emulation error");
+                    chip.v
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut chip = Chip8::with_seed(765);
+        chip.load_rom(&rom).expect("error loading rom");
+        let mut other = Chip8::with_seed(765);
+        other.load_rom(&rom).expect("error loading rom");
+        let values = run(&mut chip);
+        assert_eq!(values, run(&mut other));
+        assert!(values.windows(2).all(|w| w[0] != w[1]));
+
+        // the same numbers come again after a reset
+        chip.reset();
+        chip.load_rom(&rom).expect("error loading rom");
+        assert_eq!(run(&mut chip), values);
+
+        let mut different = Chip8::with_seed(766);
+        different.load_rom(&rom).expect("error loading rom");
+        assert_ne!(run(&mut different), values);
+    }
+
+    #[test]
+    fn collision_latched_per_frame() {
+        // LD I, 0x050; DRW V0, V1, 1; DRW V2, V1, 1; DRW V3, V1, 1
+        let rom = [0xa0, 0x50, 0xd0, 0x11, 0xd2, 0x11, 0xd3, 0x11];
+
+        for latch in [false, true] {
+            let mut chip = chip_with_rom(&rom);
+            chip.set_quirks(Quirks {
+                latch_collision_per_frame: latch,
+                ..Quirks::default()
+            });
+            chip.v[2] = 16;
+            chip.v[3] = 32;
+            chip.fb[0] = true;
+
+            // the frame ends on the first draw, which collides
+            chip.frame(10).expect("emulation error");
+            assert_eq!(chip.pc, 0x204);
+            assert_eq!(chip.v[0xf], 1);
+
+            // a draw before the next frame starts is still in the same frame
+            chip.step().expect("emulation error");
+            assert_eq!(chip.v[0xf], latch as u8);
+
+            // the latch is cleared when the next frame starts
+            chip.frame(10).expect("emulation error");
+            assert_eq!(chip.pc, 0x208);
+            assert_eq!(chip.v[0xf], 0);
+        }
+    }
+
+    #[test]
+    fn machine_routine() {
+        let mut chip = chip_with_rom(&[0x03, 0x45, 0x00, 0x00]);
+        chip.set_machine_routine_handler(Box::new(|chip, addr| {
+            chip.v[0] = (addr >> 8) as u8;
+            chip.v[1] = addr as u8;
+        }));
+
+        chip.step().expect("emulation error");
+        assert_eq!(chip.v[0], 0x03);
+        assert_eq!(chip.v[1], 0x45);
+        assert_eq!(chip.pc, 0x202);
+
+        let mut chip = chip_with_rom(&[0x03, 0x45]);
+        assert!(matches!(
+            chip.step(),
+            Err(ChipError::UnrecognizedOpcode(0x0345))
+        ));
+    }
+
+    #[test]
+    fn render_hook() {
+        // LD V0, 1; DRW V1, V1, 1; LD V0, 2; CLS
+        let mut chip = chip_with_rom(&[0x60, 0x01, 0xd1, 0x11, 0x60, 0x02, 0x00, 0xe0]);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        chip.set_render_hook(Box::new(move |fb, (width, _)| {
+            let count = counter.fetch_add(1, Ordering::Relaxed);
+            assert!(fb[..width].iter().any(|&p| p) || count == 1);
+        }));
+        chip.i = FONT_OFFSET as u16;
+
+        chip.frame(1).expect("emulation error");
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+        chip.frame(1).expect("emulation error");
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        chip.frame(1).expect("emulation error");
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        chip.frame(1).expect("emulation error");
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn random_roms_never_panic() {
+        let mut rng = StdRng::seed_from_u64(0x8);
+        for seed in 0..200 {
+            let mut rom = vec![0; rng.gen_range(2..=MAX_ROM_SIZE)];
+            rng.fill(&mut rom[..]);
+            let mut chip = Chip8::with_seed(seed);
+            chip.set_quirks(Quirks::from_bits(rng.gen()));
+            chip.load_rom(&rom).expect("error loading rom");
+
+            for _ in 0..500 {
+                chip.keypad = rng.gen();
+                if chip.frame(rng.gen_range(1..20)).is_err() {
+                    // keep going from somewhere else
+                    chip.pc = rng.gen_range(0..MEM_SIZE as u16);
+                    chip.i = rng.gen();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn max_rom_size() {
+        assert_eq!(MAX_ROM_SIZE, 0xe00);
+
+        let mut chip = Chip8::new();
+        let info = chip.load_rom(&[0xff; 0x123]).expect("error loading rom");
+        assert_eq!(info.bytes_loaded, 0x123);
+        assert_eq!(info.free_bytes, MAX_ROM_SIZE - 0x123);
+        assert_eq!(info.load_address, 0x200);
+        let info = chip
+            .load_rom(&[0xff; MAX_ROM_SIZE])
+            .expect("error loading rom");
+        assert_eq!(info.free_bytes, 0);
+        assert!(matches!(
+            chip.load_rom(&[0xff; MAX_ROM_SIZE + 1]),
+            Err(ChipError::RomTooBig(n)) if n == MAX_ROM_SIZE + 1
+        ));
+    }
+
+    #[test]
+    fn relocated_rom() {
+        // LD VA, 0x42; JP 0x600
+        let rom = [0x6a, 0x42, 0x16, 0x00];
+        let mut chip = Chip8::new();
+        let info = chip.load_rom_at(&rom, 0x600).expect("error loading rom");
+        assert_eq!(info.load_address, 0x600);
+        assert_eq!(info.free_bytes, MEM_SIZE - 0x604);
+        assert_eq!(chip.mem[0x600..0x604], rom);
+        assert_eq!(chip.mem[0x200], 0);
+        assert_eq!(chip.pc, 0x600);
+        chip.frame(2).expect("emulation error");
+        assert_eq!(chip.v[0xa], 0x42);
+        assert_eq!(chip.pc, 0x600);
+
+        // execution begins at the rom again after a reload
+        chip.reload();
+        assert_eq!(chip.pc, 0x600);
+        assert_eq!(chip.mem[0x600..0x604], rom);
+
+        assert!(matches!(
+            chip.load_rom_at(&[0xff; 0x101], 0xf00),
+            Err(ChipError::RomDoesNotFit(0xf00, 0x101))
+        ));
+        assert!(chip.load_rom_at(&[0xff; 0x100], 0xf00).is_ok());
+        assert!(matches!(
+            chip.load_rom_at(&rom, 0x100),
+            Err(ChipError::LoadAddrTooLow(0x100))
+        ));
+        assert!(matches!(
+            chip.load_rom_at(&[], 0x1000),
+            Err(ChipError::RomDoesNotFit(0x1000, 0))
+        ));
+    }
+
+    #[test]
+    fn reload() {
+        let rom = [0x60, 0x05, 0xa3, 0x00, 0xf0, 0x55];
+        let mut chip = chip_with_rom(&rom);
+        chip.frame(3).expect("emulation error");
+        chip.mem[0x201] = 0xff;
+        assert_eq!(chip.mem[0x300], 5);
+
+        chip.reload();
+        assert_eq!(chip.mem[0x200..0x206], rom);
+        assert_eq!(chip.mem[0x300], 0);
+        assert_eq!(chip.pc, 0x200);
+    }
+
+    #[test]
+    fn reset_keeping_display() {
+        // LD V0, 8; LD F, V0; DRW V0, V0, 5
+        let mut chip = chip_with_rom(&[0x60, 0x08, 0xf0, 0x29, 0xd0, 0x05]);
+        chip.frame(10).expect("emulation error");
+        let fb = chip.fb_flat().to_vec();
+        assert!(fb.iter().any(|&p| p));
+
+        chip.reset_keep_display();
+        assert_eq!(chip.fb_flat(), fb);
+        assert_eq!(chip.get_regs(), [0; 0x10]);
+        assert_eq!(chip.get_pointers(), (0x200, 0, 0));
+
+        chip.reset();
+        assert!(chip.fb_flat().iter().all(|&p| !p));
+    }
+
+    #[test]
+    fn memory_dump() {
+        let mut dump = vec![0; MEM_SIZE];
+        dump[0x300] = 0xab;
+
+        let mut chip = Chip8::new();
+        chip.pc = 0x400;
+        chip.load_memory_dump(&dump).expect("error loading dump");
+        assert_eq!(chip.mem[0x300], 0xab);
+        assert_eq!(chip.mem[FONT_OFFSET], 0);
+        assert_eq!(chip.pc, 0x200);
+
+        assert!(matches!(
+            chip.load_memory_dump(&dump[..0x200]),
+            Err(ChipError::BadDumpSize(0x200))
+        ));
+    }
+
+    #[test]
+    fn clone_diverges() {
+        // ADD V1, 1; LD I, 0x300; LD [I], V1; JP 0x200
+        let mut chip = chip_with_rom(&[0x71, 0x01, 0xa3, 0x00, 0xf1, 0x55, 0x12, 0x00]);
+        chip.frame(8).expect("emulation error");
+
+        let mut clone = chip.clone();
+        assert_eq!(clone.snapshot(), chip.snapshot());
+
+        clone.frame(4).expect("emulation error");
+        assert_eq!(clone.v[1], 3);
+        assert_eq!(clone.mem[0x301], 3);
+        assert_eq!(chip.v[1], 2);
+        assert_eq!(chip.mem[0x301], 2);
+
+        chip.frame(4).expect("emulation error");
+        assert_eq!(clone.snapshot(), chip.snapshot());
+
+        clone.key_down(4);
+        assert!(!chip.keypad[4]);
+    }
+
+    #[test]
+    fn custom_entry_point() {
+        let mut rom = vec![0; 0x402];
+        rom[0x400..].copy_from_slice(&[0x6a, 0x42]);
+        let mut chip = chip_with_rom(&rom);
+        chip.set_entry_point(0x600).expect("bad entry point");
+        assert_eq!(chip.pc, 0x600);
+
+        chip.step().expect("emulation error");
+        assert_eq!(chip.v[0xa], 0x42);
+
+        chip.reset();
+        assert_eq!(chip.pc, 0x600);
+
+        assert!(chip.set_entry_point(0xfff).is_err());
+        assert_eq!(chip.pc, 0x600);
+
+        // loading a rom keeps the entry point
+        let mut chip = Chip8::new();
+        chip.set_entry_point(0x600).expect("bad entry point");
+        chip.load_rom(&rom).expect("error loading rom");
+        assert_eq!(chip.pc, 0x600);
+        chip.step().expect("emulation error");
+        assert_eq!(chip.v[0xa], 0x42);
+        chip.reload();
+        assert_eq!(chip.pc, 0x600);
+    }
+
+    #[test]
+    fn origin_y_past_bottom() {
+        // LD V1, 40; LD I, 0x050; DRW V0, V1, 1
+        let rom = [0x61, 40, 0xa0, 0x50, 0xd0, 0x11];
+
+        let mut chip = chip_with_rom(&rom);
+        chip.frame(3).expect("emulation error");
+        assert!(chip.fb[8 * SCREEN_WIDTH]);
+        assert!(!chip.fb[31 * SCREEN_WIDTH]);
+
+        let mut chip = chip_with_rom(&rom);
+        chip.set_quirks(Quirks {
+            clamp_origin_y: true,
+            ..Quirks::default()
+        });
+        chip.frame(3).expect("emulation error");
+        assert!(!chip.fb[8 * SCREEN_WIDTH]);
+        assert!(chip.fb[31 * SCREEN_WIDTH]);
+    }
+
+    #[test]
+    fn frame_instruction_count() {
+        // LD V0, 1; DRW V0, V0, 1; JP 0x200
+        let mut chip = chip_with_rom(&[0x60, 0x01, 0xd0, 0x01, 0x12, 0x00]);
+        assert_eq!(chip.frame(10).expect("emulation error"), 2);
+        assert_eq!(chip.frame(1).expect("emulation error"), 1);
+    }
+
+    #[test]
+    fn instruction_count() {
+        // ADD V0, 1; JP 0x200; (invalid) 0xFFFF
+        let mut chip = chip_with_rom(&[0x70, 0x01, 0x12, 0x00, 0xff, 0xff]);
+        for _ in 0..7 {
+            chip.step().expect("emulation error");
+        }
+        assert_eq!(chip.instruction_count(), 7);
+        chip.frame(5).expect("emulation error");
+        assert_eq!(chip.instruction_count(), 12);
+
+        // failed instructions don't count
+        chip.pc = 0x204;
+        assert!(chip.step().is_err());
+        assert_eq!(chip.instruction_count(), 12);
+
+        chip.reset();
+        assert_eq!(chip.instruction_count(), 0);
+    }
+
+    #[test]
+    fn halt_on_self_jump() {
+        // ADD V0, 1; SE V0, 3; JP 0x200; JP 0x206
+        let rom = [0x70, 0x01, 0x30, 0x03, 0x12, 0x00, 0x12, 0x06];
+        let mut chip = chip_with_rom(&rom);
+        assert_eq!(
+            chip.run(1000, None).expect("emulation error"),
+            RunStatus::Completed
+        );
+        assert_eq!(chip.pc, 0x206);
+
+        let mut chip = chip_with_rom(&rom);
+        chip.set_stop_on_self_jump(true);
+        assert_eq!(
+            chip.run(1000, None).expect("emulation error"),
+            RunStatus::Halted
+        );
+        assert_eq!(chip.pc, 0x206);
+        assert_eq!(chip.v[0], 3);
+        assert_eq!(chip.instruction_count(), 8);
+    }
+
+    #[test]
+    fn draw_frames() {
+        // ADD V0, 1; DRW V0, V0, 1; JP 0x200
+        let mut chip = chip_with_rom(&[0x70, 0x01, 0xd0, 0x01, 0x12, 0x00]);
+        assert!(!chip.drew_last_frame());
+        chip.frame(10).expect("emulation error");
+        assert!(chip.drew_last_frame());
+        // JP 0x200; ADD V0, 1
+        chip.frame(2).expect("emulation error");
+        assert!(!chip.drew_last_frame());
+    }
+
+    #[test]
+    fn headless_run() {
+        // LD F, V0; DRW V1, V2, 5; ADD V0, 1; ADD V1, 5; SE V0, 10; JP 0x200; JP 0x20C
+        let rom = [
+            0xf0, 0x29, 0xd1, 0x25, 0x70, 0x01, 0x71, 0x05, 0x30, 0x0a, 0x12, 0x00, 0x12, 0x0c,
+        ];
+        let mut chip = chip_with_rom(&rom);
+        chip.run_frames(20, 10).expect("emulation error");
+        assert!(chip.at_self_jump());
+        // the digits from 0 to 9
+        assert_eq!(chip.fb_hash(), 0x2a1e_bd31_cd36_8807);
+    }
+
+    #[test]
+    fn frame_hashes() {
+        // LD F, V0; DRW V1, V2, 5; ADD V0, 1; ADD V1, 5; JP 0x200
+        let rom = [0xf0, 0x29, 0xd1, 0x25, 0x70, 0x01, 0x71, 0x05, 0x12, 0x00];
+
+        let hashes = chip_with_rom(&rom)
+            .run_frames_collecting(8, 10)
+            .expect("emulation error");
+        assert_eq!(hashes.len(), 8);
+        assert_ne!(hashes[0], Chip8::new().fb_hash());
+        assert_ne!(hashes[0], hashes[1]);
+
+        let again = chip_with_rom(&rom)
+            .run_frames_collecting(8, 10)
+            .expect("emulation error");
+        assert_eq!(hashes, again);
+    }
+
+    #[test]
+    fn deadline() {
+        // JP 0x200
+        let mut chip = chip_with_rom(&[0x12, 0x00]);
+        let past = Some(Instant::now());
+        assert_eq!(
+            chip.run(usize::MAX, past).expect("emulation error"),
+            RunStatus::DeadlineExceeded
+        );
+        assert_eq!(
+            chip.frame_with_deadline(usize::MAX, past)
+                .expect("emulation error"),
+            RunStatus::DeadlineExceeded
+        );
+
+        let future = Some(Instant::now() + Duration::from_secs(60));
+        assert_eq!(
+            chip.run(100, future).expect("emulation error"),
+            RunStatus::Completed
+        );
+        assert_eq!(
+            chip.frame_with_deadline(100, None)
+                .expect("emulation error"),
+            RunStatus::Completed
+        );
+    }
+
+    #[test]
+    fn pal_timers() {
+        let mut chip = Chip8::new();
+        assert_eq!(chip.frame_period(), Duration::from_nanos(16_666_666));
+
+        chip.set_timer_hz(50).expect("bad timer rate");
+        assert_eq!(chip.timer_hz(), 50);
+        assert_eq!(chip.frame_period(), Duration::from_millis(20));
+
+        assert!(matches!(
+            chip.set_timer_hz(0),
+            Err(ChipError::ZeroTimerRate)
+        ));
+        assert_eq!(chip.frame_period(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn ticks() {
+        // LD V0, 2; LD ST, V0; SKP V1; JP 0x204; LD F, V1; DRW V0, V0, 5; DRW V0, V0, 5; JP 0x20E
+        let rom = [
+            0x60, 0x02, 0xf0, 0x18, 0xe1, 0x9e, 0x12, 0x04, 0xf1, 0x29, 0xd0, 0x05, 0xd0, 0x05,
+            0x12, 0x0e,
+        ];
+        let mut chip = chip_with_rom(&rom);
+        let tick = chip.tick(10, 0).expect("emulation error");
+        assert_eq!(
+            tick,
+            TickResult {
+                framebuffer_changed: false,
+                buzzer_on: true,
+                drew: false
+            }
+        );
+
+        // pressing key V1 leaves the loop, the buzzer ends next frame
+        let tick = chip.tick(10, 0b1).expect("emulation error");
+        assert_eq!(
+            tick,
+            TickResult {
+                framebuffer_changed: true,
+                buzzer_on: true,
+                drew: true
+            }
+        );
+        assert!(chip.keypad[0]);
+
+        // erased by the second draw: modified, but no different
+        let tick = chip.tick(10, 0).expect("emulation error");
+        assert!(tick.framebuffer_changed && tick.drew && !tick.buzzer_on);
+        assert!(!chip.keypad[0]);
+        assert!(chip.fb.iter().all(|&p| !p));
+
+        let tick = chip.tick(10, 0).expect("emulation error");
+        assert_eq!(tick, TickResult::default());
+    }
+
+    #[test]
+    fn timer_tick_positions() {
+        // LD V1, DT; JP 0x200
+        let rom = [0xf1, 0x07, 0x12, 0x00];
+        let mut chip = chip_with_rom(&rom);
+        chip.dt = 3;
+        chip.frame(2).expect("emulation error");
+        assert_eq!(chip.v[1], 2);
+        assert_eq!(chip.get_timers().0, 2);
+
+        let mut chip = chip_with_rom(&rom);
+        chip.set_timer_tick_position(TimerTickPosition::FrameEnd);
+        chip.dt = 3;
+        chip.frame(2).expect("emulation error");
+        assert_eq!(chip.v[1], 3);
+        assert_eq!(chip.get_timers().0, 2);
+        chip.frame(2).expect("emulation error");
+        assert_eq!(chip.v[1], 2);
+        assert_eq!(chip.get_timers().0, 1);
+    }
+
+    #[test]
+    fn manual_timers() {
+        // JP 0x200
+        let mut chip = chip_with_rom(&[0x12, 0x00]);
+        chip.set_timer_tick_position(TimerTickPosition::Manual);
+        chip.dt = 10;
+        chip.st = 1;
+        chip.run(1000, None).expect("emulation error");
+        for _ in 0..5 {
+            chip.frame(100).expect("emulation error");
+        }
+        assert_eq!(chip.get_timers(), (10, 1));
+
+        chip.tick_timers();
+        assert_eq!(chip.get_timers(), (9, 0));
+        chip.tick_timers();
+        assert_eq!(chip.get_timers(), (8, 0));
+    }
+
+    #[test]
+    fn resolution_switch() {
+        // HIGH; LD V0, 100; LD V1, 60; LD I, 0x050; DRW V0, V1, 5; LOW
+        let rom = [
+            0x00, 0xff, 0x60, 100, 0x61, 60, 0xa0, 0x50, 0xd0, 0x15, 0x00, 0xfe,
+        ];
+        let mut chip = chip_with_rom(&rom);
+        chip.step().expect("emulation error");
+        assert_eq!(chip.dimensions(), (128, 64));
+        assert_eq!(chip.fb().len(), 64);
+
+        chip.run(4, None).expect("emulation error");
+        // the 0 sprite, clipped at the bottom of the screen
+        let rows: Vec<&[bool]> = chip.fb().skip(60).collect();
+        assert_eq!(rows[0][100..104], [true; 4]);
+        assert_eq!(rows[3][100..104], [true, false, false, true]);
+
+        chip.step().expect("emulation error");
+        assert_eq!(chip.dimensions(), (64, 32));
+        assert!(chip.fb_flat().iter().all(|&p| !p));
+    }
+
+    #[test]
+    fn big_sprite() {
+        // LD I, 0x208; DRW V0, V1, 0; DRW V0, V1, 0; JP 0x206; 16 rows of 0xFF01
+        let mut rom = vec![0xa2, 0x08, 0xd0, 0x10, 0xd0, 0x10, 0x12, 0x06];
+        for _ in 0..16 {
+            rom.extend([0xff, 0x01]);
+        }
+
+        // nothing to draw in the low resolution
+        let mut chip = chip_with_rom(&rom);
+        chip.run(2, None).expect("emulation error");
+        assert!(chip.fb_flat().iter().all(|&p| !p));
+
+        let mut chip = chip_with_rom(&rom);
+        chip.set_resolution(true);
+        chip.v[0] = 8;
+        chip.v[1] = 48;
+        chip.run(2, None).expect("emulation error");
+        assert_eq!(chip.v[VF], 0);
+        let mut row = [false; 16];
+        row[..8].fill(true);
+        row[15] = true;
+        let rows: Vec<&[bool]> = chip.fb().collect();
+        assert!(rows[47].iter().all(|&p| !p));
+        for row_pixels in &rows[48..] {
+            assert_eq!(row_pixels[8..24], row);
+        }
+
+        // clipped at the right edge, then erased
+        chip.opcode_cls();
+        chip.v[0] = 120;
+        chip.v[1] = 0;
+        chip.pc = 0x202;
+        chip.step().expect("emulation error");
+        assert!(chip.fb().take(16).all(|row| row[120..] == [true; 8]));
+        chip.step().expect("emulation error");
+        assert_eq!(chip.v[VF], 1);
+        assert!(chip.fb_flat().iter().all(|&p| !p));
+
+        // the 32 bytes must fit in memory
+        chip.i = 0xff0;
+        chip.pc = 0x202;
+        assert!(matches!(
+            chip.step(),
+            Err(ChipError::AddrOutOfBounds(0xff0))
+        ));
+    }
+
+    #[test]
+    fn origin_past_right_edge() {
+        // LD V1, 70; DRW V1, V2, 1
+        let rom = [0x61, 70, 0xd1, 0x21];
+
+        for clip in [false, true] {
+            let mut chip = chip_with_rom(&rom);
+            chip.set_quirks(Quirks {
+                clip_origin: clip,
+                ..Quirks::default()
+            });
+            chip.i = FONT_OFFSET as u16;
+            chip.step().expect("emulation error");
+            chip.step().expect("emulation error");
+            assert_eq!(chip.fb[6], !clip);
+            assert!(chip.fb.iter().any(|&p| p) != clip);
+        }
+    }
+
+    #[test]
+    fn keypad_latched_per_frame() {
+        // LD V1, 1; SKP V0; LD V1, 2
+        let rom = [0x61, 0x01, 0xe0, 0x9e, 0x61, 0x02];
+
+        for latch in [false, true] {
+            let mut chip = chip_with_rom(&rom);
+            chip.set_quirks(Quirks {
+                latch_keypad_per_frame: latch,
+                ..Quirks::default()
+            });
+            chip.frame(1).expect("emulation error");
+            chip.key_down(0);
+            chip.step().expect("emulation error");
+            assert_eq!(chip.pc, if latch { 0x204 } else { 0x206 });
+        }
+    }
+
+    #[test]
+    fn flat_framebuffer() {
+        let mut chip = Chip8::new();
+        for high in [false, true] {
+            chip.set_resolution(high);
+            chip.set_fb(5, 3, true).expect("debug error");
+            let (width, height) = chip.dimensions();
+            let flat = chip.fb_flat();
+            assert_eq!(flat.len(), width * height);
+            for (y, row) in chip.fb().enumerate() {
+                assert_eq!(row, &flat[y * width..(y + 1) * width]);
+            }
+            assert!(flat[3 * width + 5]);
+        }
+
+        const SIZE: (usize, usize) = (Chip8::screen_width(), Chip8::screen_height());
+        chip.set_resolution(false);
+        assert_eq!(chip.dimensions(), SIZE);
+    }
+
+    #[test]
+    fn rgba_framebuffer() {
+        let mut chip = Chip8::new();
+        chip.fb[1] = true;
+        let fg = [0x11, 0x22, 0x33, 0xff];
+        let bg = [0, 0, 0, 0xff];
+
+        let rgba = chip.fb_to_rgba(fg, bg);
+        assert_eq!(rgba.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        assert_eq!(rgba[0..4], bg);
+        assert_eq!(rgba[4..8], fg);
+        assert_eq!(rgba[8..12], bg);
+    }
+
+    #[test]
+    fn screen_persistence() {
+        // LD F, V0; DRW V0, V0, 5; DRW V0, V0, 5; JP 0x206
+        let mut chip = chip_with_rom(&[0xf0, 0x29, 0xd0, 0x05, 0xd0, 0x05, 0x12, 0x06]);
+        assert_eq!(chip.persistence(), None);
+        chip.set_persistence(3);
+
+        chip.frame(10).expect("emulation error");
+        assert!(chip.fb_flat()[0]);
+        assert_eq!(chip.persistence().expect("persistence disabled")[0], 0xff);
+
+        // erased, then fading for 3 frames
+        let mut brightness = Vec::new();
+        for _ in 0..4 {
+            chip.frame(10).expect("emulation error");
+            brightness.push(chip.persistence().expect("persistence disabled")[0]);
+        }
+        assert!(!chip.fb_flat()[0]);
+        assert_eq!(brightness, [191, 127, 63, 0]);
+
+        chip.set_persistence(0);
+        assert_eq!(chip.persistence(), None);
+    }
+
+    #[test]
+    fn zero_opcode() {
+        let mut chip = chip_with_rom(&[0x00, 0x00]);
+        assert!(matches!(
+            chip.step(),
+            Err(ChipError::UnrecognizedOpcode(0x0000))
+        ));
+
+        chip.set_quirks(Quirks {
+            treat_zero_as_nop: true,
+            ..Quirks::default()
+        });
+        chip.step().expect("emulation error");
+        assert_eq!(chip.pc, 0x202);
+    }
+
     #[test]
     fn jump() {
         let mut chip = chip_with_rom(&[0x13, 0x21, 0x00, 0x00, 0x00, 0x00]);
@@ -455,6 +2104,73 @@ mod tests {
         assert_eq!(chip.pc, 0x321);
     }
 
+    #[test]
+    fn jump_with_offset() {
+        // LD V0, 0x04; JP V0, 0x200
+        let mut chip = chip_with_rom(&[0x60, 0x04, 0xb2, 0x00]);
+        chip.step().expect("emulation error");
+        chip.step().expect("emulation error");
+        assert_eq!(chip.pc, 0x204);
+
+        // LD V0, 0xFF; JP V0, 0xF00
+        let mut chip = chip_with_rom(&[0x60, 0xff, 0xbf, 0x00]);
+        chip.step().expect("emulation error");
+        chip.step().expect("emulation error");
+        assert_eq!(chip.pc, 0xfff);
+        assert!(matches!(chip.step(), Err(ChipError::PcOutOfBounds(0xfff))));
+    }
+
+    #[test]
+    fn jump_out_of_memory() {
+        // LD V0, 0x20; JP V0, 0xFF0
+        let rom = [0x60, 0x20, 0xbf, 0xf0];
+
+        let mut chip = chip_with_rom(&rom);
+        chip.step().expect("emulation error");
+        chip.step().expect("emulation error");
+        assert_eq!(chip.pc, 0x010);
+
+        let mut chip = chip_with_rom(&rom);
+        chip.set_quirks(Quirks {
+            validate_jump_targets: true,
+            ..Quirks::default()
+        });
+        chip.step().expect("emulation error");
+        assert!(matches!(chip.step(), Err(ChipError::PcOutOfBounds(0x1010))));
+        assert_eq!(chip.pc, 0x202);
+
+        // JP 0xFFF; CALL 0xFFF, leaving no room for a whole opcode
+        for rom in [[0x1f, 0xff], [0x2f, 0xff]] {
+            let mut chip = chip_with_rom(&rom);
+            chip.step().expect("emulation error");
+            assert!(matches!(chip.step(), Err(ChipError::PcOutOfBounds(0xfff))));
+
+            let mut chip = chip_with_rom(&rom);
+            chip.set_quirks(Quirks {
+                validate_jump_targets: true,
+                ..Quirks::default()
+            });
+            assert!(matches!(chip.step(), Err(ChipError::PcOutOfBounds(0xfff))));
+            assert_eq!(chip.pc, 0x200);
+        }
+    }
+
+    #[test]
+    fn odd_jump_hook() {
+        // JP 0x203; 0x00; CALL 0x205; JP 0x208
+        let mut chip = chip_with_rom(&[0x12, 0x03, 0x00, 0x22, 0x05, 0x12, 0x08]);
+        let jumps = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = jumps.clone();
+        chip.set_odd_jump_hook(Box::new(move |pc, target| {
+            seen.lock().unwrap().push((pc, target));
+        }));
+        for _ in 0..3 {
+            chip.step().expect("emulation error");
+        }
+        assert_eq!(chip.pc, 0x208);
+        assert_eq!(*jumps.lock().unwrap(), [(0x200, 0x203), (0x203, 0x205)]);
+    }
+
     #[test]
     fn call_and_return() {
         let mut chip = chip_with_rom(&[0x22, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xee]);
@@ -465,6 +2181,85 @@ mod tests {
         assert_eq!(chip.pc, 0x202);
     }
 
+    #[test]
+    fn return_with_empty_stack() {
+        let mut chip = chip_with_rom(&[0x00, 0xee]);
+        assert!(matches!(chip.step(), Err(ChipError::SpOutOfBounds(0))));
+        assert_eq!(chip.sp, 0);
+    }
+
+    #[test]
+    fn full_call_chain() {
+        // CALL 0x204, CALL 0x208, ... 16 deep, each followed by RET
+        let mut rom = Vec::new();
+        for n in 1..=16u16 {
+            rom.extend((0x2200 + n * 4).to_be_bytes());
+            rom.extend([0x00, 0xee]);
+        }
+        rom.extend([0x00, 0xee]);
+        let mut chip = chip_with_rom(&rom);
+        chip.run(16, None).expect("emulation error");
+        assert_eq!(chip.sp, 16);
+        assert_eq!(chip.stack[0], 0x200);
+        assert_eq!(chip.stack[15], 0x23c);
+        assert_eq!(chip.pc, 0x240);
+
+        chip.run(16, None).expect("emulation error");
+        assert_eq!(chip.sp, 0);
+        assert_eq!(chip.pc, 0x202);
+    }
+
+    #[test]
+    fn call_stack_overflow() {
+        // CALL 0x200, over and over
+        let mut chip = chip_with_rom(&[0x22, 0x00]);
+        for _ in 0..16 {
+            chip.step().expect("emulation error");
+        }
+        assert_eq!(chip.sp, 16);
+        assert!(matches!(chip.step(), Err(ChipError::SpOutOfBounds(16))));
+        assert_eq!(chip.sp, 16);
+    }
+
+    #[test]
+    fn stack_overflow_policies() {
+        // CALL 0x202, CALL 0x204, ...
+        let rom: Vec<u8> = (0..20u16)
+            .flat_map(|n| (0x2202 + 2 * n).to_be_bytes())
+            .collect();
+
+        let mut chip = chip_with_rom(&rom);
+        for _ in 0..16 {
+            chip.step().expect("emulation error");
+        }
+        assert!(matches!(chip.step(), Err(ChipError::SpOutOfBounds(16))));
+
+        let mut chip = chip_with_rom(&rom);
+        chip.set_quirks(Quirks {
+            stack_overflow: StackOverflowPolicy::Wrap,
+            ..Quirks::default()
+        });
+        for _ in 0..18 {
+            chip.step().expect("emulation error");
+        }
+        assert_eq!(chip.sp, 2);
+        assert_eq!(chip.stack[0], 0x220);
+        assert_eq!(chip.stack[1], 0x222);
+        assert_eq!(chip.stack[2], 0x204);
+
+        let mut chip = chip_with_rom(&rom);
+        chip.set_quirks(Quirks {
+            stack_overflow: StackOverflowPolicy::Ignore,
+            ..Quirks::default()
+        });
+        for _ in 0..18 {
+            chip.step().expect("emulation error");
+        }
+        assert_eq!(chip.sp, 16);
+        assert_eq!(chip.stack[15], 0x21e);
+        assert_eq!(chip.pc, 0x224);
+    }
+
     #[test]
     fn opcodes_skp_sknp() {
         let mut chip = chip_with_rom(&[
@@ -494,7 +2289,29 @@ mod tests {
         let mut chip = chip_with_rom(&[0xf0, 0x0a, 0x00, 0x00]);
         chip.step().expect("emulation error");
         chip.step().expect("emulation error");
+        assert_eq!(chip.pc, 0x200);
+
+        // held keys keep it waiting
+        chip.key_down(5);
+        chip.key_down(8);
+        for _ in 0..3 {
+            chip.step().expect("emulation error");
+            assert_eq!(chip.pc, 0x200);
+        }
+
+        chip.key_up(8);
         chip.step().expect("emulation error");
+        assert_eq!(chip.pc, 0x202);
+        assert_eq!(chip.v[0], 8);
+    }
+
+    #[test]
+    fn load_from_keypad_on_press() {
+        let mut chip = chip_with_rom(&[0xf0, 0x0a, 0x00, 0x00]);
+        chip.set_quirks(Quirks {
+            ld_k_on_press: true,
+            ..Quirks::default()
+        });
         chip.step().expect("emulation error");
         assert_eq!(chip.pc, 0x200);
 
@@ -505,6 +2322,130 @@ mod tests {
         assert_eq!(chip.v[0], 5);
     }
 
+    #[test]
+    fn load_from_keypad_pc() {
+        // LD V0, 1; LD V1, K; LD V2, 2
+        let mut chip = chip_with_rom(&[0x60, 0x01, 0xf1, 0x0a, 0x62, 0x02]);
+        chip.step().expect("emulation error");
+        assert_eq!(chip.pc, 0x202);
+
+        // waiting: pc stays on the instruction, whole frames included
+        for _ in 0..3 {
+            assert_eq!(chip.step().expect("emulation error"), 0xf10a);
+            assert_eq!(chip.pc, 0x202);
+        }
+        assert_eq!(chip.frame(10).expect("emulation error"), 10);
+        assert_eq!(chip.pc, 0x202);
+        assert_eq!(chip.v[1], 0);
+
+        // resolved: pc moves to the next instruction only once
+        chip.key_down(0xb);
+        assert_eq!(chip.step().expect("emulation error"), 0xf10a);
+        assert_eq!(chip.pc, 0x202);
+        chip.key_up(0xb);
+        assert_eq!(chip.step().expect("emulation error"), 0xf10a);
+        assert_eq!(chip.pc, 0x204);
+        assert_eq!(chip.v[1], 0xb);
+        assert_eq!(chip.step().expect("emulation error"), 0x6202);
+        assert_eq!(chip.pc, 0x206);
+        assert_eq!(chip.v[2], 2);
+    }
+
+    #[test]
+    fn load_from_keypad_pc_mid_frame() {
+        // LD V0, K; ADD V1, 1; JP 0x202
+        let mut chip = chip_with_rom(&[0xf0, 0x0a, 0x71, 0x01, 0x12, 0x02]);
+        chip.frame(4).expect("emulation error");
+        assert_eq!(chip.pc, 0x200);
+
+        chip.key_down(7);
+        chip.frame(4).expect("emulation error");
+        assert_eq!(chip.pc, 0x200);
+
+        // the release is read by the first instruction of the frame,
+        // the rest of the frame runs past it
+        chip.key_up(7);
+        chip.frame(4).expect("emulation error");
+        assert_eq!(chip.v[0], 7);
+        assert_eq!(chip.v[1], 2);
+        assert_eq!(chip.pc, 0x204);
+    }
+
+    #[test]
+    fn load_from_keypad_policies() {
+        for (policy, key) in [(LdKPolicy::LowestIndex, 3), (LdKPolicy::MostRecent, 5)] {
+            let mut chip = chip_with_rom(&[0xf0, 0x0a]);
+            chip.set_quirks(Quirks {
+                ld_k_policy: policy,
+                ..Quirks::default()
+            });
+            chip.key_down(3);
+            chip.key_down(5);
+            // a key held down doesn't count as pressed again
+            chip.key_down(3);
+            chip.step().expect("emulation error");
+            chip.key_up(3);
+            chip.key_up(5);
+            chip.step().expect("emulation error");
+            assert_eq!(chip.v[0], key);
+        }
+    }
+
+    #[test]
+    fn random_masking() {
+        // RND V0, 0x0F; RND V1, 0x00; RND V2, 0xA5; JP 0x200
+        let mut chip = chip_with_rom(&[0xc0, 0x0f, 0xc1, 0x00, 0xc2, 0xa5, 0x12, 0x00]);
+        let mut seen = [0u8; 3];
+        for _ in 0..1000 {
+            chip.run(4, None).expect("emulation error");
+            assert!(chip.v[0] <= 0x0f);
+            assert_eq!(chip.v[1], 0);
+            assert_eq!(chip.v[2] & !0xa5, 0);
+            for (seen, &v) in seen.iter_mut().zip(&chip.v) {
+                *seen |= v;
+            }
+        }
+        // every bit allowed by the masks came up
+        assert_eq!(seen, [0x0f, 0x00, 0xa5]);
+    }
+
+    #[test]
+    fn bcd() {
+        let mut chip = chip_with_rom(&[0xf0, 0x33, 0xf0, 0x33]);
+        chip.v[0] = 219;
+        chip.i = 0x300;
+        chip.step().expect("emulation error");
+        assert_eq!(chip.mem[0x300..0x303], [2, 1, 9]);
+
+        // the digits would go past the end of memory
+        chip.i = 0xffe;
+        assert!(matches!(
+            chip.step(),
+            Err(ChipError::AddrOutOfBounds(0xffe))
+        ));
+        chip.i = 0xfff;
+        assert!(chip.step().is_err());
+    }
+
+    #[test]
+    fn i_past_end_of_memory() {
+        // DRW V0, V0, 5; LD [I], V2; LD V2, [I]
+        for op in [0xd005u16, 0xf255, 0xf265] {
+            let mut chip = chip_with_rom(&op.to_be_bytes());
+            chip.i = 0xffe;
+            assert!(
+                matches!(chip.step(), Err(ChipError::AddrOutOfBounds(0xffe))),
+                "{:04X}",
+                op
+            );
+        }
+
+        // the last bytes of memory can still be used
+        let mut chip = chip_with_rom(&[0xd0, 0x02, 0xf1, 0x55]);
+        chip.i = 0xffe;
+        chip.run(2, None).expect("emulation error");
+    }
+
     #[test]
     fn mass_store() {
         let mut chip = chip_with_rom(&[0xf3, 0x55]);
@@ -541,6 +2482,143 @@ mod tests {
         assert_eq!(chip.v[4], 0x00);
     }
 
+    #[test]
+    fn reads_below_font() {
+        for (zero_below_font, expected) in [(false, [0xab, 0xcd]), (true, [0, 0])] {
+            // LD V1, [I]; DRW V2, V2, 1
+            let mut chip = chip_with_rom(&[0xf1, 0x65, 0xd2, 0x21]);
+            chip.set_quirks(Quirks {
+                zero_below_font,
+                ..Quirks::default()
+            });
+            chip.mem[0x010] = 0xab;
+            chip.mem[0x011] = 0xcd;
+            chip.i = 0x010;
+            chip.step().expect("emulation error");
+            assert_eq!(chip.v[..2], expected);
+            chip.step().expect("emulation error");
+            assert_eq!(chip.fb_flat()[0], !zero_below_font);
+        }
+
+        // the font itself is always readable
+        let mut chip = chip_with_rom(&[0xf1, 0x65]);
+        chip.set_quirks(Quirks {
+            zero_below_font: true,
+            ..Quirks::default()
+        });
+        chip.i = FONT_OFFSET as u16 - 1;
+        chip.step().expect("emulation error");
+        assert_eq!(chip.v[..2], [0, FONT_SPRITES[0]]);
+    }
+
+    #[test]
+    fn logic_vf_reset() {
+        // OR V0, V1; AND V0, V1; XOR V0, V1
+        let rom = [0x80, 0x11, 0x80, 0x12, 0x80, 0x13];
+
+        for reset in [false, true] {
+            let mut chip = chip_with_rom(&rom);
+            chip.set_quirks(Quirks {
+                logic_resets_vf: reset,
+                ..Quirks::default()
+            });
+            for _ in 0..3 {
+                chip.v[0xf] = 1;
+                chip.step().expect("emulation error");
+                assert_eq!(chip.v[0xf], !reset as u8);
+            }
+        }
+    }
+
+    #[test]
+    fn opcodes_writing_vf() {
+        // (opcode, writes VF), with x = 1 and y = 2
+        let opcodes = [
+            (0x6133u16, false),
+            (0x7133, false),
+            (0x8120, false),
+            (0x8121, false),
+            (0x8122, false),
+            (0x8123, false),
+            (0x8124, true),
+            (0x8125, true),
+            (0x8126, true),
+            (0x8127, true),
+            (0x812e, true),
+            (0xa300, false),
+            (0xc133, false),
+            (0xd125, true),
+            (0xf107, false),
+            (0xf115, false),
+            (0xf118, false),
+            (0xf11e, false),
+            (0xf129, false),
+            (0xf133, false),
+            (0xf255, false),
+            (0xf265, false),
+        ];
+
+        for (op, writes) in opcodes {
+            let mut chip = chip_with_rom(&op.to_be_bytes());
+            chip.v[1] = 0x11;
+            chip.v[2] = 0x22;
+            chip.v[VF] = 0xaa;
+            chip.i = 0x300;
+            chip.step().expect("emulation error");
+            assert_eq!(chip.v[VF] != 0xaa, writes, "{:04X}", op);
+        }
+    }
+
+    #[test]
+    fn flag_register_as_operand() {
+        // ADD VF, VF; ADD VF, V0; SUB V0, VF
+        let rom = [0x8f, 0xf4, 0x8f, 0x04, 0x80, 0xf5];
+
+        for (quirk, results) in [(false, [1, 0, 1]), (true, [0x00, 0x20, 1])] {
+            let mut chip = chip_with_rom(&rom);
+            chip.set_quirks(Quirks {
+                flag_before_result: quirk,
+                ..Quirks::default()
+            });
+            chip.v[0xf] = 0x80;
+            chip.v[0] = 0x20;
+
+            chip.step().expect("emulation error");
+            assert_eq!(chip.v[0xf], results[0]);
+            chip.step().expect("emulation error");
+            assert_eq!(chip.v[0xf], results[1]);
+            chip.step().expect("emulation error");
+            assert_eq!(chip.v[0xf], results[2]);
+        }
+    }
+
+    #[test]
+    fn flag_register_as_destination() {
+        // (opcode, VF, V1, result), with x = F and y = 1; the flag is always 1
+        let opcodes = [
+            (0x8f14u16, 0x80, 0x90, 0x10),
+            (0x8f15, 0x30, 0x10, 0x20),
+            (0x8f16, 0x05, 0x00, 0x02),
+            (0x8f17, 0x10, 0x30, 0x20),
+            (0x8f1e, 0x81, 0x00, 0x02),
+        ];
+
+        for (op, vf, v1, result) in opcodes {
+            for flag_before_result in [false, true] {
+                let mut chip = chip_with_rom(&op.to_be_bytes());
+                chip.set_quirks(Quirks {
+                    flag_before_result,
+                    ..Quirks::default()
+                });
+                chip.v[VF] = vf;
+                chip.v[1] = v1;
+                chip.step().expect("emulation error");
+                let expected = if flag_before_result { result } else { 1 };
+                assert_eq!(chip.v[VF], expected, "{:04X}", op);
+            }
+        }
+    }
+
     #[test]
     fn shift_right_left() {
         let mut chip = chip_with_rom(&[0x82, 0x36, 0x86, 0x3e]);
@@ -558,4 +2636,42 @@ mod tests {
         assert_eq!(chip.v[0xf], 1);
         assert_eq!(chip.v[3], 0b10101010);
     }
+
+    #[test]
+    fn shift_and_mass_quirks() {
+        let vip = Quirks {
+            shift_uses_vy: true,
+            mass_load_store_increments_i: true,
+            jump_with_vx: true,
+            ..Quirks::default()
+        };
+
+        // SHR V2 {, V3}; SHL V2 {, V3}
+        let mut chip = chip_with_rom(&[0x82, 0x36, 0x82, 0x3e]);
+        chip.set_quirks(vip);
+        chip.v[2] = 0xff;
+        chip.v[3] = 0b00000110;
+        chip.step().expect("emulation error");
+        assert_eq!(chip.v[2], 0b00000011);
+        assert_eq!(chip.v[0xf], 0);
+        chip.step().expect("emulation error");
+        assert_eq!(chip.v[2], 0b00001100);
+
+        // LD [I], V3; LD V3, [I]
+        for (quirks, i) in [(Quirks::default(), 0x220), (vip, 0x228)] {
+            let mut chip = chip_with_rom(&[0xf3, 0x55, 0xf3, 0x65]);
+            chip.set_quirks(quirks);
+            chip.i = 0x220;
+            chip.step().expect("emulation error");
+            chip.step().expect("emulation error");
+            assert_eq!(chip.i, i);
+        }
+
+        // LD V2, 0x04; JP V2, 0x200
+        let mut chip = chip_with_rom(&[0x62, 0x04, 0xb2, 0x00]);
+        chip.set_quirks(vip);
+        chip.step().expect("emulation error");
+        chip.step().expect("emulation error");
+        assert_eq!(chip.pc, 0x204);
+    }
 }
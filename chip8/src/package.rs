@@ -0,0 +1,130 @@
+use alloc::vec::Vec;
+
+use crate::constants::MAX_ROM_SIZE;
+use crate::error::ChipError;
+use crate::quirks::Quirks;
+use crate::Chip8;
+
+/// The bytes every package starts with.
+pub const PACKAGE_MAGIC: [u8; 4] = *b"C8PK";
+/// The package format version written by `pack`.
+pub const PACKAGE_VERSION: u8 = 1;
+
+const HEADER_SIZE: usize = 13;
+
+/// The settings stored in a package alongside the rom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackageInfo {
+    pub quirks: Quirks,
+    /// Recommended instructions per frame.
+    pub ipf: u16,
+    /// Size of the rom in bytes.
+    pub rom_size: usize,
+}
+
+/// Bundles a rom with its quirks and recommended instructions per frame.
+///
+/// The layout is the magic bytes, the version, the quirk bitfield (u32),
+/// the ipf (u16), the rom size (u16) and the rom itself.
+/// Numbers are little endian.
+///
+/// Roms that don't fit in memory are refused with `RomTooBig`.
+pub fn pack(rom: &[u8], quirks: Quirks, ipf: u16) -> Result<Vec<u8>, ChipError> {
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(ChipError::RomTooBig(rom.len()));
+    }
+    let mut data = Vec::with_capacity(HEADER_SIZE + rom.len());
+    data.extend_from_slice(&PACKAGE_MAGIC);
+    data.push(PACKAGE_VERSION);
+    data.extend_from_slice(&quirks.to_bits().to_le_bytes());
+    data.extend_from_slice(&ipf.to_le_bytes());
+    data.extend_from_slice(&(rom.len() as u16).to_le_bytes());
+    data.extend_from_slice(rom);
+    Ok(data)
+}
+
+/// Reads the header of a package, returning its settings and the rom.
+pub fn unpack(data: &[u8]) -> Result<(PackageInfo, &[u8]), ChipError> {
+    if data.len() < HEADER_SIZE {
+        return Err(ChipError::InvalidPackage("truncated header"));
+    }
+    if data[0..4] != PACKAGE_MAGIC {
+        return Err(ChipError::InvalidPackage("bad magic"));
+    }
+    if data[4] != PACKAGE_VERSION {
+        return Err(ChipError::InvalidPackage("unsupported version"));
+    }
+
+    let quirks = Quirks::from_bits(u32::from_le_bytes([data[5], data[6], data[7], data[8]]));
+    let ipf = u16::from_le_bytes([data[9], data[10]]);
+    let rom_size = u16::from_le_bytes([data[11], data[12]]) as usize;
+
+    let rom = &data[HEADER_SIZE..];
+    if rom.len() != rom_size {
+        return Err(ChipError::InvalidPackage("rom size mismatch"));
+    }
+
+    Ok((
+        PackageInfo {
+            quirks,
+            ipf,
+            rom_size,
+        },
+        rom,
+    ))
+}
+
+impl Chip8 {
+    /// Loads a package created by `pack`.
+    ///
+    /// The quirks are applied and the rom is loaded in memory.
+    /// The recommended ipf is returned for the frontend to use.
+    pub fn load_package(&mut self, data: &[u8]) -> Result<PackageInfo, ChipError> {
+        let (info, rom) = unpack(data)?;
+        self.load_rom(rom)?;
+        self.set_quirks(info.quirks);
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_and_load() {
+        let quirks = Quirks {
+            logic_resets_vf: true,
+            ..Quirks::default()
+        };
+        let data = pack(&[0x80, 0x11], quirks, 15).expect("error packing rom");
+
+        let mut chip = Chip8::new();
+        let info = chip.load_package(&data).expect("error loading package");
+        assert_eq!(info.ipf, 15);
+        assert_eq!(info.rom_size, 2);
+        assert_eq!(chip.quirks(), quirks);
+
+        // 8011 should reset VF
+        chip.set_reg(0xf, 1).expect("debug error");
+        chip.step().expect("emulation error");
+        assert_eq!(chip.get_regs()[0xf], 0);
+    }
+
+    #[test]
+    fn bad_package() {
+        let mut chip = Chip8::new();
+        let mut data = pack(&[0x00, 0xe0], Quirks::default(), 10).expect("error packing rom");
+        data[0] = b'X';
+        assert!(chip.load_package(&data).is_err());
+        assert!(chip.load_package(&data[..5]).is_err());
+
+        // the size would not fit in the header, and the rom not in memory
+        assert!(matches!(
+            pack(&[0; 0x10001], Quirks::default(), 10),
+            Err(ChipError::RomTooBig(0x10001))
+        ));
+        assert!(pack(&[0; MAX_ROM_SIZE + 1], Quirks::default(), 10).is_err());
+        assert!(pack(&[0; MAX_ROM_SIZE], Quirks::default(), 10).is_ok());
+    }
+}
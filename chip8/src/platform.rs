@@ -0,0 +1,45 @@
+use crate::decode::Instruction;
+use crate::Chip8;
+
+/// The Chip-8 variants the emulator knows about.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Platform {
+    /// The original Chip-8 instruction set.
+    #[default]
+    Chip8,
+    /// SUPER-CHIP, adding a high resolution mode and more instructions.
+    SuperChip,
+}
+
+impl Platform {
+    /// Returns true if the emulator can run the instruction on this platform.
+    ///
+    /// `0NNN` is only supported through a machine routine handler,
+    /// so it is never reported as supported.
+    /// Of the SUPER-CHIP instructions, only the resolution switches
+    /// and the 16x16 sprites are implemented yet.
+    pub fn supports(self, ins: Instruction) -> bool {
+        match ins {
+            Instruction::Sys(_) => false,
+            Instruction::Low | Instruction::High | Instruction::Drw(_, _, 0) => {
+                self == Platform::SuperChip
+            }
+            _ => !ins.is_schip(),
+        }
+    }
+}
+
+impl Chip8 {
+    /// Returns the platform the rom is run as.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Sets the platform the rom is run as.
+    ///
+    /// It decides which instructions `set_schip_warnings` warns about,
+    /// it doesn't change how they run.
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+    }
+}
@@ -0,0 +1,155 @@
+/// What `2NNN` does when the stack is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StackOverflowPolicy {
+    /// Returns `ChipError::SpOutOfBounds`.
+    #[default]
+    Error,
+    /// Wraps around, overwriting the oldest return address.
+    Wrap,
+    /// Jumps without saving the return address.
+    Ignore,
+}
+
+/// Which key `FX0A` reads when several keys are released at once,
+/// or pressed with the `ld_k_on_press` quirk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LdKPolicy {
+    /// The key with the lowest index.
+    #[default]
+    LowestIndex,
+    /// The key that went down last.
+    MostRecent,
+}
+
+/// Behaviors that differ between Chip-8 interpreters.
+///
+/// The default values follow the modern CHIP-48 interpreter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6` and `8XYE` copy `Vy` into `Vx` before shifting.
+    pub shift_uses_vy: bool,
+    /// `FX55` and `FX65` leave `I` incremented by `x + 1`.
+    pub mass_load_store_increments_i: bool,
+    /// `BNNN` jumps to `NNN + Vx`, where `x` is the high nibble of `NNN`.
+    pub jump_with_vx: bool,
+    /// `8XY1`, `8XY2` and `8XY3` reset `VF` to 0, like the COSMAC VIP.
+    /// Later interpreters leave it unchanged.
+    pub logic_resets_vf: bool,
+    /// Memory outside the font is filled with random bytes on reset,
+    /// like the uninitialized RAM of real hardware.
+    pub randomize_memory: bool,
+    /// Once a `DXYN` collides, `VF` stays set for the following draws
+    /// until the next frame.
+    pub latch_collision_per_frame: bool,
+    /// Jumps and calls to addresses with no room for an opcode,
+    /// `0xFFF` and past it, return an error instead of failing on the next fetch.
+    pub validate_jump_targets: bool,
+    /// `DXYN` clamps a `Vy` past the bottom of the screen to the last row,
+    /// instead of wrapping it around.
+    pub clamp_origin_y: bool,
+    /// `DXYN` draws nothing when `Vx` or `Vy` is past the edge of the screen,
+    /// instead of wrapping the origin around.
+    pub clip_origin: bool,
+    /// `0000` does nothing, instead of being an unrecognized opcode,
+    /// so roms padded with zeros don't crash when running into them.
+    pub treat_zero_as_nop: bool,
+    /// `DXYN` and `FX65` read 0 below the font, like interpreters
+    /// that had nothing mapped there, instead of the bytes in memory.
+    pub zero_below_font: bool,
+    /// The keypad is read once at the start of each frame, so every
+    /// instruction in a frame sees the same keys, like replayed input.
+    pub latch_keypad_per_frame: bool,
+    /// What `2NNN` does when the stack is full.
+    pub stack_overflow: StackOverflowPolicy,
+    /// Which key `FX0A` reads when several keys are released at once.
+    pub ld_k_policy: LdKPolicy,
+    /// `FX0A` completes as soon as a key is down, instead of waiting
+    /// for a key to be released like the COSMAC VIP.
+    pub ld_k_on_press: bool,
+    /// `8XY4`, `8XY5`, `8XY6`, `8XY7` and `8XYE` write `VF` before `Vx`,
+    /// so with `VF` as destination the result overwrites the flag.
+    pub flag_before_result: bool,
+}
+
+impl Quirks {
+    /// Returns the quirks packed in a bitfield, one bit per quirk.
+    pub fn to_bits(self) -> u32 {
+        let flags = [
+            self.logic_resets_vf,
+            self.randomize_memory,
+            self.latch_collision_per_frame,
+            self.validate_jump_targets,
+            self.clamp_origin_y,
+            self.latch_keypad_per_frame,
+        ];
+        let bits = flags
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (n, &f)| bits | (f as u32) << n);
+        bits | (self.stack_overflow as u32) << 6
+            | (self.ld_k_policy as u32) << 8
+            | (self.flag_before_result as u32) << 9
+            | (self.clip_origin as u32) << 10
+            | (self.treat_zero_as_nop as u32) << 11
+            | (self.zero_below_font as u32) << 12
+            | (self.shift_uses_vy as u32) << 13
+            | (self.mass_load_store_increments_i as u32) << 14
+            | (self.jump_with_vx as u32) << 15
+            | (self.ld_k_on_press as u32) << 16
+    }
+
+    /// Builds the quirks from a bitfield created by `to_bits`.
+    /// Unknown bits are ignored.
+    pub fn from_bits(bits: u32) -> Self {
+        let flag = |n: u32| bits & (1 << n) != 0;
+        Quirks {
+            shift_uses_vy: flag(13),
+            mass_load_store_increments_i: flag(14),
+            jump_with_vx: flag(15),
+            logic_resets_vf: flag(0),
+            randomize_memory: flag(1),
+            latch_collision_per_frame: flag(2),
+            validate_jump_targets: flag(3),
+            clamp_origin_y: flag(4),
+            latch_keypad_per_frame: flag(5),
+            stack_overflow: match (bits >> 6) & 0b11 {
+                1 => StackOverflowPolicy::Wrap,
+                2 => StackOverflowPolicy::Ignore,
+                _ => StackOverflowPolicy::Error,
+            },
+            ld_k_policy: match (bits >> 8) & 0b1 {
+                1 => LdKPolicy::MostRecent,
+                _ => LdKPolicy::LowestIndex,
+            },
+            flag_before_result: flag(9),
+            clip_origin: flag(10),
+            treat_zero_as_nop: flag(11),
+            zero_below_font: flag(12),
+            ld_k_on_press: flag(16),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_round_trip() {
+        let quirks = Quirks {
+            shift_uses_vy: true,
+            logic_resets_vf: true,
+            latch_keypad_per_frame: true,
+            stack_overflow: StackOverflowPolicy::Ignore,
+            ld_k_policy: LdKPolicy::MostRecent,
+            flag_before_result: true,
+            clip_origin: true,
+            treat_zero_as_nop: true,
+            zero_below_font: true,
+            ld_k_on_press: true,
+            ..Quirks::default()
+        };
+        assert_eq!(Quirks::from_bits(quirks.to_bits()), quirks);
+        assert_eq!(Quirks::from_bits(0), Quirks::default());
+    }
+}
@@ -0,0 +1,46 @@
+//! Small roms embedded in the library, so frontends can offer
+//! something to run without any file.
+//!
+//! The roms were written for this project, and share its license.
+
+/// The embedded roms, by name.
+const ROMS: [(&str, &[u8]); 2] = [
+    // shows the 16 digits of the font
+    ("digits", include_bytes!("../roms/digits.ch8")),
+    // shows the last key pressed
+    ("keypad", include_bytes!("../roms/keypad.ch8")),
+];
+
+/// Returns the names and bytes of the embedded roms.
+pub fn list() -> &'static [(&'static str, &'static [u8])] {
+    &ROMS
+}
+
+/// Returns the embedded rom with the given name.
+pub fn get(name: &str) -> Option<&'static [u8]> {
+    ROMS.iter().find(|(n, _)| *n == name).map(|&(_, rom)| rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn run_embedded_rom() {
+        assert_eq!(list().len(), 2);
+        assert!(get("missing").is_none());
+
+        let mut chip = Chip8::new();
+        chip.load_rom(get("digits").expect("rom not embedded"))
+            .expect("error loading rom");
+        for _ in 0..20 {
+            chip.frame(10).expect("emulation error");
+        }
+        // the top row of 0, at (2, 2)
+        let (width, _) = chip.dimensions();
+        assert_eq!(chip.fb_flat()[2 * width + 2..2 * width + 6], [true; 4]);
+        // stuck on the final jump
+        assert_eq!(chip.get_pointers().0, 0x218);
+    }
+}
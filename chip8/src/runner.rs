@@ -0,0 +1,287 @@
+use std::sync::mpsc::{
+    channel, sync_channel, Receiver, RecvTimeoutError, SendError, Sender, SyncSender, TrySendError,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::error::ChipError;
+use crate::Chip8;
+
+/// A command sent to a `Runner`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Resets the machine and loads the rom.
+    ///
+    /// If the rom can't be loaded, the error is sent back
+    /// and the runner waits for another rom.
+    Load(Vec<u8>),
+    /// Presses a key. A key past `0xF` is sent back as an error.
+    KeyDown(usize),
+    /// Releases a key. A key past `0xF` is sent back as an error.
+    KeyUp(usize),
+    /// Pauses or resumes the emulation.
+    Pause(bool),
+    /// Runs a single frame, useful while paused.
+    Step,
+    /// Ends the emulation thread.
+    Quit,
+}
+
+/// The screen at the end of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The pixels, row by row.
+    pub pixels: Vec<bool>,
+    pub width: usize,
+    pub height: usize,
+    pub buzzer: bool,
+}
+
+/// Runs a `Chip8` on its own thread, one frame per timer tick.
+///
+/// The machine is driven with `Command`s, and a `Frame` is sent back
+/// after every frame, so the emulation timing doesn't depend on the UI.
+/// Only one frame waits to be received: while it waits,
+/// the following frames are dropped.
+///
+/// No frame runs until a rom is loaded, with `Command::Load`
+/// or before spawning the runner.
+#[derive(Debug)]
+pub struct Runner {
+    commands: Sender<Command>,
+    frames: Receiver<Frame>,
+    errors: Receiver<ChipError>,
+    thread: Option<JoinHandle<Result<Chip8, ChipError>>>,
+}
+
+impl Runner {
+    /// Starts running the machine, `ipf` instructions per frame,
+    /// as soon as it has a rom.
+    pub fn spawn(chip: Chip8, ipf: usize) -> Self {
+        let (commands, command_rx) = channel();
+        let (frame_tx, frames) = sync_channel(1);
+        let (error_tx, errors) = channel();
+        let thread = thread::spawn(move || run(chip, ipf, command_rx, frame_tx, error_tx));
+        Runner {
+            commands,
+            frames,
+            errors,
+            thread: Some(thread),
+        }
+    }
+
+    /// Sends a command to the emulation thread.
+    /// Fails if the thread has ended.
+    pub fn send(&self, command: Command) -> Result<(), SendError<Command>> {
+        self.commands.send(command)
+    }
+
+    /// Returns the receiving end of the frames.
+    pub fn frames(&self) -> &Receiver<Frame> {
+        &self.frames
+    }
+
+    /// Returns the receiving end of the errors of commands
+    /// that didn't stop the thread, like a rom too big to load.
+    pub fn errors(&self) -> &Receiver<ChipError> {
+        &self.errors
+    }
+
+    /// Ends the emulation thread, returning the machine,
+    /// or the error that stopped it.
+    pub fn stop(mut self) -> Result<Chip8, ChipError> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<Chip8, ChipError> {
+        // the thread may have already ended on an error
+        let _ = self.commands.send(Command::Quit);
+        self.thread
+            .take()
+            .expect("runner already stopped")
+            .join()
+            .expect("runner thread panicked")
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            let _ = self.join();
+        }
+    }
+}
+
+/// The loop of the emulation thread.
+fn run(
+    mut chip: Chip8,
+    ipf: usize,
+    commands: Receiver<Command>,
+    frames: SyncSender<Frame>,
+    errors: Sender<ChipError>,
+) -> Result<Chip8, ChipError> {
+    let mut paused = false;
+    let mut loaded = !chip.rom.is_empty();
+    let mut next_frame = Instant::now();
+
+    loop {
+        // wait for the next frame, or forever while paused or without a rom
+        let idle = paused || !loaded;
+        let command = if idle {
+            commands.recv().ok()
+        } else {
+            match commands.recv_timeout(next_frame.saturating_duration_since(Instant::now())) {
+                Ok(command) => Some(command),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return Ok(chip),
+            }
+        };
+
+        let mut step = false;
+        match command {
+            Some(Command::Load(rom)) => {
+                chip.reset();
+                loaded = match chip.load_rom(&rom) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        let _ = errors.send(e);
+                        false
+                    }
+                };
+                next_frame = Instant::now();
+                continue;
+            }
+            Some(Command::KeyDown(k) | Command::KeyUp(k)) if k > 0xf => {
+                let _ = errors.send(ChipError::NoKey(k));
+                continue;
+            }
+            Some(Command::KeyDown(k)) => {
+                chip.key_down(k);
+                continue;
+            }
+            Some(Command::KeyUp(k)) => {
+                chip.key_up(k);
+                continue;
+            }
+            Some(Command::Pause(pause)) => {
+                paused = pause;
+                next_frame = Instant::now();
+                continue;
+            }
+            Some(Command::Step) => step = true,
+            Some(Command::Quit) => return Ok(chip),
+            // the senders were dropped while idle
+            None if idle => return Ok(chip),
+            None => next_frame += chip.frame_period(),
+        }
+        if !loaded || (paused && !step) {
+            continue;
+        }
+
+        chip.frame(ipf)?;
+        let (width, height) = chip.dimensions();
+        let frame = Frame {
+            pixels: chip.save_display(),
+            width,
+            height,
+            buzzer: chip.buzzer(),
+        };
+        // the receiver hasn't caught up, it gets the next frame instead
+        if let Err(TrySendError::Disconnected(_)) = frames.try_send(frame) {
+            return Ok(chip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn run_on_thread() {
+        let runner = Runner::spawn(Chip8::new(), 10);
+        // LD F, V0; DRW V0, V0, 5; JP 0x204
+        runner
+            .send(Command::Load(vec![0xf0, 0x29, 0xd0, 0x05, 0x12, 0x04]))
+            .expect("runner stopped");
+
+        let frame = loop {
+            let frame = runner
+                .frames()
+                .recv_timeout(Duration::from_secs(5))
+                .expect("no frame received");
+            if frame.pixels.iter().any(|&p| p) {
+                break frame;
+            }
+        };
+        assert_eq!((frame.width, frame.height), (64, 32));
+        // top row of the 0 sprite
+        assert_eq!(frame.pixels[..5], [true, true, true, true, false]);
+
+        runner.send(Command::Pause(true)).expect("runner stopped");
+        let chip = runner.stop().expect("emulation error");
+        assert_eq!(chip.get_pointers().0, 0x204);
+    }
+
+    #[test]
+    fn bad_rom() {
+        let runner = Runner::spawn(Chip8::new(), 10);
+        runner
+            .send(Command::Load(vec![0; 0x1000]))
+            .expect("runner stopped");
+        let error = runner
+            .errors()
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no error received");
+        assert!(matches!(error, ChipError::RomTooBig(0x1000)));
+        // nothing runs until a rom is loaded
+        assert!(runner
+            .frames()
+            .recv_timeout(Duration::from_millis(50))
+            .is_err());
+
+        // JP 0x200
+        runner
+            .send(Command::Load(vec![0x12, 0x00]))
+            .expect("runner stopped");
+        runner
+            .frames()
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no frame received");
+        let chip = runner.stop().expect("emulation error");
+        assert_eq!(chip.get_pointers().0, 0x200);
+    }
+
+    #[test]
+    fn bad_key() {
+        let runner = Runner::spawn(Chip8::new(), 10);
+        runner.send(Command::KeyDown(0x10)).expect("runner stopped");
+        let error = runner
+            .errors()
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no error received");
+        assert!(matches!(error, ChipError::NoKey(0x10)));
+
+        runner.send(Command::KeyDown(0xf)).expect("runner stopped");
+        let chip = runner.stop().expect("emulation error");
+        assert!(chip.get_keypad()[0xf]);
+    }
+
+    #[test]
+    fn frames_wait_for_receiver() {
+        let runner = Runner::spawn(Chip8::new(), 10);
+        // JP 0x200
+        runner
+            .send(Command::Load(vec![0x12, 0x00]))
+            .expect("runner stopped");
+        thread::sleep(Duration::from_millis(100));
+        runner.send(Command::Pause(true)).expect("runner stopped");
+        thread::sleep(Duration::from_millis(50));
+        // the frames run while nobody received them were dropped
+        assert!(runner.frames().try_recv().is_ok());
+        assert!(runner.frames().try_recv().is_err());
+        runner.stop().expect("emulation error");
+    }
+}
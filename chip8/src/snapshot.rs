@@ -0,0 +1,445 @@
+use alloc::vec::Vec;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::constants::{
+    HIRES_HEIGHT, HIRES_WIDTH, MEM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, STACK_DEPTH,
+};
+use crate::error::ChipError;
+use crate::quirks::Quirks;
+use crate::Chip8;
+
+/// The bytes every save state starts with.
+pub const STATE_MAGIC: [u8; 4] = *b"C8ST";
+/// The save state format version written by `to_bytes`.
+/// States of other versions are rejected.
+pub const STATE_VERSION: u8 = 3;
+
+/// A plain copy of the machine state.
+///
+/// Useful to set up an exact starting point in tests and bug reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateSnapshot {
+    pub mem: [u8; MEM_SIZE],
+    /// The frame buffer, row by row, sized for the resolution given by `hires`.
+    pub fb: Vec<bool>,
+    pub hires: bool,
+    pub v: [u8; 0x10],
+    pub i: u16,
+    pub dt: u8,
+    pub st: u8,
+    pub pc: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    pub keypad: [bool; 16],
+    pub quirks: Quirks,
+    /// The seed of the random number generator, used again on reset.
+    pub seed: u64,
+    /// How many 32 bit words the random number generator produced since seeded.
+    pub rng_word_pos: u128,
+    /// The keypad after the last instruction, for `FX0A` to see released keys.
+    pub prev_keypad: [bool; 16],
+    /// The keypad at the start of the frame.
+    pub frame_keypad: [bool; 16],
+    /// How many times a key went down.
+    pub key_presses: u64,
+    /// The value of `key_presses` when each key last went down.
+    pub key_order: [u64; 16],
+}
+
+impl Default for StateSnapshot {
+    fn default() -> Self {
+        Chip8::new().snapshot()
+    }
+}
+
+/// Splits the first `n` bytes off `data`.
+fn take<'a>(data: &mut &'a [u8], n: usize) -> Result<&'a [u8], ChipError> {
+    if data.len() < n {
+        return Err(ChipError::InvalidState("truncated"));
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_u16(data: &mut &[u8]) -> Result<u16, ChipError> {
+    let bytes = take(data, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn take_u64(data: &mut &[u8]) -> Result<u64, ChipError> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(take(data, 8)?);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Packs the keys in a u16, one bit per key.
+fn keys_to_bits(keys: &[bool; 16]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .fold(0, |bits, (k, &p)| bits | (p as u16) << k)
+}
+
+fn take_keys(data: &mut &[u8]) -> Result<[bool; 16], ChipError> {
+    let bits = take_u16(data)?;
+    let mut keys = [false; 16];
+    for (k, p) in keys.iter_mut().enumerate() {
+        *p = bits & (1 << k) != 0;
+    }
+    Ok(keys)
+}
+
+impl StateSnapshot {
+    /// Returns the snapshot in a compact binary form.
+    ///
+    /// The layout is the magic bytes, the version, the quirk bitfield (u32),
+    /// the memory, `hires`, the registers, `i` (u16), `dt`, `st`, `pc` (u16),
+    /// `sp`, the stack (u16 each), the keypad (u16, one bit per key),
+    /// the seed (u64), `rng_word_pos` (u128), `prev_keypad` and `frame_keypad`
+    /// (u16 each, like the keypad), `key_presses` (u64), `key_order` (u64 each)
+    /// and the frame buffer, eight pixels per byte.
+    /// Numbers are little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(MEM_SIZE + 0x600);
+        data.extend_from_slice(&STATE_MAGIC);
+        data.push(STATE_VERSION);
+        data.extend_from_slice(&self.quirks.to_bits().to_le_bytes());
+        data.extend_from_slice(&self.mem);
+        data.push(self.hires as u8);
+        data.extend_from_slice(&self.v);
+        data.extend_from_slice(&self.i.to_le_bytes());
+        data.push(self.dt);
+        data.push(self.st);
+        data.extend_from_slice(&self.pc.to_le_bytes());
+        data.push(self.sp);
+        for addr in self.stack {
+            data.extend_from_slice(&addr.to_le_bytes());
+        }
+        data.extend_from_slice(&keys_to_bits(&self.keypad).to_le_bytes());
+        data.extend_from_slice(&self.seed.to_le_bytes());
+        data.extend_from_slice(&self.rng_word_pos.to_le_bytes());
+        data.extend_from_slice(&keys_to_bits(&self.prev_keypad).to_le_bytes());
+        data.extend_from_slice(&keys_to_bits(&self.frame_keypad).to_le_bytes());
+        data.extend_from_slice(&self.key_presses.to_le_bytes());
+        for order in self.key_order {
+            data.extend_from_slice(&order.to_le_bytes());
+        }
+        for pixels in self.fb.chunks(8) {
+            data.push(
+                pixels
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (n, &p)| byte | (p as u8) << (7 - n)),
+            );
+        }
+        data
+    }
+
+    /// Reads a snapshot written by `to_bytes`.
+    pub fn from_bytes(mut data: &[u8]) -> Result<Self, ChipError> {
+        let data = &mut data;
+        if take(data, 4)? != STATE_MAGIC {
+            return Err(ChipError::InvalidState("bad magic"));
+        }
+        let version = take(data, 1)?[0];
+        if version != STATE_VERSION {
+            return Err(ChipError::InvalidState("unsupported version"));
+        }
+        let quirks = take(data, 4)?;
+        let quirks = Quirks::from_bits(u32::from_le_bytes([
+            quirks[0], quirks[1], quirks[2], quirks[3],
+        ]));
+        let mut mem = [0; MEM_SIZE];
+        mem.copy_from_slice(take(data, MEM_SIZE)?);
+        let hires = take(data, 1)?[0] != 0;
+        let mut v = [0; 0x10];
+        v.copy_from_slice(take(data, 0x10)?);
+        let i = take_u16(data)?;
+        let dt = take(data, 1)?[0];
+        let st = take(data, 1)?[0];
+        let pc = take_u16(data)?;
+        let sp = take(data, 1)?[0];
+        if sp as usize > STACK_DEPTH {
+            return Err(ChipError::InvalidState("bad stack pointer"));
+        }
+        let mut stack = [0; 16];
+        for addr in stack.iter_mut() {
+            *addr = take_u16(data)?;
+        }
+        let keypad = take_keys(data)?;
+        let seed = take_u64(data)?;
+        let mut rng_word_pos = [0; 16];
+        rng_word_pos.copy_from_slice(take(data, 16)?);
+        let rng_word_pos = u128::from_le_bytes(rng_word_pos);
+        let prev_keypad = take_keys(data)?;
+        let frame_keypad = take_keys(data)?;
+        let key_presses = take_u64(data)?;
+        let mut key_order = [0; 16];
+        for order in key_order.iter_mut() {
+            *order = take_u64(data)?;
+        }
+
+        let (width, height) = if hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        };
+        let fb = take(data, width * height / 8)?
+            .iter()
+            .flat_map(|byte| (0..8).map(move |n| byte & (0x80 >> n) != 0))
+            .collect();
+        if !data.is_empty() {
+            return Err(ChipError::InvalidState("trailing bytes"));
+        }
+
+        Ok(StateSnapshot {
+            mem,
+            fb,
+            hires,
+            v,
+            i,
+            dt,
+            st,
+            pc,
+            sp,
+            stack,
+            keypad,
+            quirks,
+            seed,
+            rng_word_pos,
+            prev_keypad,
+            frame_keypad,
+            key_presses,
+            key_order,
+        })
+    }
+}
+
+/// Snapshots are serialized as the bytes of `to_bytes`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for StateSnapshot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StateSnapshot {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StateVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for StateVisitor {
+            type Value = StateSnapshot;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("the bytes of a save state")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+                StateSnapshot::from_bytes(bytes).map_err(E::custom)
+            }
+
+            // formats without a byte type, like JSON, write a sequence
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut bytes = Vec::new();
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                self.visit_bytes(&bytes)
+            }
+        }
+
+        deserializer.deserialize_bytes(StateVisitor)
+    }
+}
+
+/// The machine is serialized as its `StateSnapshot`, so handlers, hooks
+/// and debugging settings are left out, like with `save_state`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chip8 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snapshot().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Chip8 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        StateSnapshot::deserialize(deserializer).map(|snapshot| Chip8::from_snapshot(&snapshot))
+    }
+}
+
+impl Chip8 {
+    /// Returns a new instance in the state described by the snapshot.
+    pub fn from_snapshot(snapshot: &StateSnapshot) -> Self {
+        let mut chip = Chip8::with_seed(snapshot.seed);
+        chip.restore(snapshot);
+        chip
+    }
+
+    /// Puts the machine in the state described by the snapshot.
+    ///
+    /// Handlers, hooks and debugging settings are kept.
+    pub fn restore(&mut self, snapshot: &StateSnapshot) {
+        self.mem = snapshot.mem;
+        self.fb = snapshot.fb.clone();
+        self.hires = snapshot.hires;
+        self.v = snapshot.v;
+        self.i = snapshot.i;
+        self.dt = snapshot.dt;
+        self.st = snapshot.st;
+        self.pc = snapshot.pc;
+        self.sp = snapshot.sp;
+        self.stack = snapshot.stack;
+        self.keypad = snapshot.keypad;
+        self.quirks = snapshot.quirks;
+        self.seed = snapshot.seed;
+        self.rng = ChaCha12Rng::seed_from_u64(snapshot.seed);
+        self.rng.set_word_pos(snapshot.rng_word_pos);
+        self.prev_keypad = snapshot.prev_keypad;
+        self.frame_keypad = snapshot.frame_keypad;
+        self.key_presses = snapshot.key_presses;
+        self.key_order = snapshot.key_order;
+        self.fb_dirty = true;
+    }
+
+    /// Returns the machine state in the binary form of `StateSnapshot::to_bytes`,
+    /// to be written to a file.
+    pub fn save_state(&self) -> Vec<u8> {
+        self.snapshot().to_bytes()
+    }
+
+    /// Restores a state saved with `save_state`.
+    /// The machine is left untouched if the state is invalid.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), ChipError> {
+        self.restore(&StateSnapshot::from_bytes(state)?);
+        Ok(())
+    }
+
+    /// Returns a copy of the machine state.
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            mem: self.mem,
+            fb: self.fb.clone(),
+            hires: self.hires,
+            v: self.v,
+            i: self.i,
+            dt: self.dt,
+            st: self.st,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack,
+            keypad: self.keypad,
+            quirks: self.quirks,
+            seed: self.seed,
+            rng_word_pos: self.rng.get_word_pos(),
+            prev_keypad: self.prev_keypad,
+            frame_keypad: self.frame_keypad,
+            key_presses: self.key_presses,
+            key_order: self.key_order,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_snapshot() {
+        let mut snapshot = StateSnapshot::default();
+        snapshot.mem[0x300] = 0xab;
+        snapshot.fb[3 * 64 + 5] = true;
+        snapshot.v[0xc] = 0x42;
+        snapshot.i = 0x321;
+        snapshot.dt = 7;
+        snapshot.st = 9;
+        snapshot.pc = 0x2a0;
+        snapshot.sp = 2;
+        snapshot.stack[1] = 0x204;
+        snapshot.stack[2] = 0x254;
+        snapshot.keypad[0xe] = true;
+        snapshot.quirks.logic_resets_vf = true;
+
+        let chip = Chip8::from_snapshot(&snapshot);
+        assert_eq!(chip.get_mem(), snapshot.mem);
+        assert_eq!(chip.fb_flat(), snapshot.fb);
+        assert_eq!(chip.get_regs(), snapshot.v);
+        assert_eq!(chip.get_pointers(), (0x2a0, 2, 0x321));
+        assert_eq!(chip.get_timers(), (7, 9));
+        assert_eq!(chip.get_stack(), snapshot.stack);
+        assert_eq!(chip.get_keypad(), snapshot.keypad);
+        assert_eq!(chip.quirks(), snapshot.quirks);
+        assert_eq!(chip.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn save_and_load_state() {
+        let mut chip = Chip8::with_seed(0);
+        // LD V0, 3; LD F, V0; DRW V0, V0, 5; CALL 0x200
+        chip.load_rom(&[0x60, 0x03, 0xf0, 0x29, 0xd0, 0x05, 0x22, 0x00])
+            .expect("error loading rom");
+        chip.frame(10).expect("emulation error");
+        chip.frame(2).expect("emulation error");
+        chip.key_down(0xc);
+        let state = chip.save_state();
+
+        let mut other = Chip8::new();
+        other.load_state(&state).expect("error loading state");
+        assert_eq!(other.snapshot(), chip.snapshot());
+
+        assert!(matches!(
+            other.load_state(&state[..state.len() - 1]),
+            Err(ChipError::InvalidState(_))
+        ));
+        let mut bad = state.clone();
+        bad[0] = b'X';
+        assert!(other.load_state(&bad).is_err());
+        let mut old = state.clone();
+        old[4] = STATE_VERSION - 1;
+        assert!(other.load_state(&old).is_err());
+        assert_eq!(other.snapshot(), chip.snapshot());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        // RND V0, 0xFF; RND V1, 0xFF; LD V2, K; JP 0x200
+        let rom = [0xc0, 0xff, 0xc1, 0xff, 0xf2, 0x0a, 0x12, 0x00];
+        let mut chip = Chip8::new();
+        chip.load_rom(&rom).expect("error loading rom");
+        chip.frame(10).expect("emulation error");
+        // saved with the key down, so LD K completes when it goes up
+        chip.key_down(0x5);
+        chip.frame(10).expect("emulation error");
+        let state = bincode::serialize(&chip).expect("serialization error");
+
+        let play = |chip: &mut Chip8| {
+            let mut frames = Vec::new();
+            for n in 0..6 {
+                match n {
+                    0 => chip.key_up(0x5),
+                    2 => chip.key_down(0x9),
+                    3 => chip.key_up(0x9),
+                    _ => {}
+                }
+                chip.frame(10).expect("emulation error");
+                frames.push(chip.snapshot());
+            }
+            frames
+        };
+        let frames = play(&mut chip);
+        assert_eq!(frames[0].v[2], 0x5);
+        assert_eq!(frames[3].v[2], 0x9);
+
+        let mut other: Chip8 = bincode::deserialize(&state).expect("deserialization error");
+        assert_eq!(play(&mut other), frames);
+
+        assert!(bincode::deserialize::<Chip8>(&state[..state.len() - 1]).is_err());
+    }
+}
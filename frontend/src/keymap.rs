@@ -0,0 +1,73 @@
+use sdl2::keyboard::Keycode;
+
+/// The keyboard key bound to each Chip-8 key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyMap {
+    keys: [Keycode; 16],
+}
+
+impl Default for KeyMap {
+    /// The left side of a QWERTY keyboard, laid out like the COSMAC VIP keypad.
+    fn default() -> Self {
+        KeyMap {
+            keys: [
+                Keycode::X,
+                Keycode::Num1,
+                Keycode::Num2,
+                Keycode::Num3,
+                Keycode::Q,
+                Keycode::W,
+                Keycode::E,
+                Keycode::A,
+                Keycode::S,
+                Keycode::D,
+                Keycode::Z,
+                Keycode::C,
+                Keycode::Num4,
+                Keycode::R,
+                Keycode::F,
+                Keycode::V,
+            ],
+        }
+    }
+}
+
+impl KeyMap {
+    /// Returns the Chip-8 key bound to a keyboard key.
+    pub fn chip_key(&self, code: Keycode) -> Option<usize> {
+        self.keys.iter().position(|&c| c == code)
+    }
+
+    /// Binds a keyboard key to Chip-8 key `k`.
+    /// If the keyboard key was bound to another Chip-8 key,
+    /// that one gets the old key of `k`, so no key is left unbound.
+    pub fn bind(&mut self, k: usize, code: Keycode) {
+        if let Some(other) = self.chip_key(code) {
+            self.keys[other] = self.keys[k];
+        }
+        self.keys[k] = code;
+    }
+
+    /// Returns each Chip-8 key with the name of its keyboard key,
+    /// like `(0x1, "1")`, for help screens.
+    pub fn describe(&self) -> Vec<(usize, String)> {
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(k, code)| (k, code.name()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_default() {
+        let keys = KeyMap::default().describe();
+        assert_eq!(keys.len(), 16);
+        assert_eq!(keys[0x1], (0x1, "1".to_string()));
+        assert_eq!(keys[0xf], (0xf, "V".to_string()));
+    }
+}
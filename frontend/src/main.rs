@@ -1,54 +1,96 @@
 use sdl2::audio::{AudioCallback, AudioSpecDesired};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
+use std::error::Error;
 use std::fs;
 use std::io::Read;
-use std::time::Duration;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use clap::Parser;
 
 use chip8::Chip8;
 
-pub const SQUARE_SIZE: usize = 16;
-pub const SCREEN_WIDTH: usize = 64;
-pub const SCREEN_HEIGHT: usize = 32;
+mod keymap;
 
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
-struct Args {
-    /// Rom to open
-    #[clap(short, long)]
-    rom: Option<String>,
+mod recording;
+use recording::{AudioRecording, Samples};
 
-    /// Instructions per frame
-    #[clap(long, default_value_t = 10)]
-    ipf: usize,
+mod settings;
+use settings::{Args, Settings, Waveform};
+
+mod slots;
+
+pub const SCANLINE_COLOR: Color = Color::RGBA(0, 0, 0, 96);
+pub const WINDOW_TITLE: &str = "Rusty Chip";
+
+/// The steps of rebinding a key from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rebind {
+    /// Not rebinding.
+    Off,
+    /// Waiting for the current key of the Chip-8 key to rebind.
+    Choose,
+    /// Waiting for the new key of Chip-8 key `k`.
+    Bind(usize),
+}
+
+impl Rebind {
+    /// Returns the window title prompting for the next step.
+    fn prompt(self) -> String {
+        match self {
+            Rebind::Off => WINDOW_TITLE.to_string(),
+            Rebind::Choose => format!("{} - press the key to rebind", WINDOW_TITLE),
+            Rebind::Bind(k) => format!("{} - press the new key for {:X}", WINDOW_TITLE, k),
+        }
+    }
 }
 
-struct SquareWave {
+/// Returns true if the key has its own function, so it can't be bound
+/// to a Chip-8 key: quitting, rebinding, the controls, pausing,
+/// stepping and the save state slots.
+fn is_reserved(code: Keycode) -> bool {
+    matches!(
+        code,
+        Keycode::Escape | Keycode::F9 | Keycode::F10 | Keycode::P | Keycode::N
+    ) || slots::slot(code).is_some()
+}
+
+struct Tone {
+    waveform: Waveform,
     phase_inc: f32,
     phase: f32,
     volume: f32,
+    /// Plays silence, used instead of pausing the device while recording.
+    muted: bool,
+    recording: Option<Samples>,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Tone {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
         for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
+            *x = if self.muted {
+                0.0
             } else {
-                -self.volume
+                self.volume * self.waveform.sample(self.phase)
             };
             self.phase = (self.phase + self.phase_inc) % 1.0;
         }
+        if let Some(recording) = &self.recording {
+            recording
+                .lock()
+                .expect("main thread panicked")
+                .extend_from_slice(out);
+        }
     }
 }
 
+/// Reads the whole rom file at `path`.
 fn get_rom(path: &str) -> Vec<u8> {
     let mut rom = vec![];
     fs::OpenOptions::new()
@@ -62,43 +104,73 @@ fn get_rom(path: &str) -> Vec<u8> {
 }
 
 fn main() {
-    // Parse arguments
-    let args = Args::parse();
-    let ipf = args.ipf;
+    run(Settings::from(Args::parse()));
+}
+
+/// Runs the emulator until the window is closed.
+fn run(settings: Settings) {
+    let ipf = settings.ipf;
+    let scale = settings.scale;
 
     let mut chip = Chip8::new();
+    if settings.pal {
+        chip.set_timer_hz(50).expect("bad timer rate");
+    }
 
     // initialize SDL stuff
     let sdl_context = sdl2::init().expect("couldn't initialize SDL");
     let video_subsystem = sdl_context
         .video()
         .expect("couldn't initialize the video subsystem");
-    let audio_subsystem = sdl_context
-        .audio()
-        .expect("couldn't initialize the audio subsystem");
-
     let desired_spec = AudioSpecDesired {
         freq: Some(44100),
         channels: Some(1), // mono
         samples: None,     // default sample size
     };
 
-    let sound = audio_subsystem
-        .open_playback(None, &desired_spec, |spec| {
+    let samples = settings.record_audio.as_ref().map(|_| Samples::default());
+    // without a device, the emulator runs silently
+    let mut sound = match sdl_context.audio().and_then(|audio_subsystem| {
+        audio_subsystem.open_playback(None, &desired_spec, |spec| {
             // initialize the audio callback
-            SquareWave {
+            Tone {
+                waveform: settings.waveform,
                 phase_inc: 440.0 / spec.freq as f32,
                 phase: 0.0,
-                volume: 0.1,
+                volume: settings.volume,
+                muted: false,
+                recording: samples.clone(),
             }
         })
-        .expect("couldn't open audio device");
+    }) {
+        Ok(sound) => Some(sound),
+        Err(e) => {
+            eprintln!("couldn't open audio device, running without sound: {}", e);
+            None
+        }
+    };
+    // saved when returning from this function
+    let recording = match (&sound, settings.record_audio.clone(), samples) {
+        (Some(sound), Some(path), Some(samples)) => {
+            Some(AudioRecording::new(path, sound.spec().freq as u32, samples))
+        }
+        (None, Some(_), _) => {
+            eprintln!("no audio device, the audio won't be recorded");
+            None
+        }
+        _ => None,
+    };
+    if let (Some(sound), Some(_)) = (&mut sound, &recording) {
+        // the silence is recorded too, so the device never pauses
+        sound.lock().muted = true;
+        sound.resume();
+    }
 
     let window = video_subsystem
         .window(
-            "Rusty Chip",
-            (SQUARE_SIZE * SCREEN_WIDTH) as u32,
-            (SQUARE_SIZE * SCREEN_HEIGHT) as u32,
+            WINDOW_TITLE,
+            (scale * Chip8::screen_width()) as u32,
+            (scale * Chip8::screen_height()) as u32,
         )
         .position_centered()
         .build()
@@ -109,14 +181,15 @@ fn main() {
         .present_vsync()
         .build()
         .expect("could not make a canvas");
-    canvas.set_draw_color(Color::BLACK);
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(settings.bg);
     canvas.clear();
     canvas.present();
 
     let mut event_pump = sdl_context.event_pump().expect("event pump error");
 
     // Open and load rom
-    let path = if let Some(path) = args.rom {
+    let mut path = if let Some(path) = settings.rom {
         path
     } else {
         let path;
@@ -146,102 +219,208 @@ fn main() {
     let rom = get_rom(&path);
     chip.load_rom(&rom).expect("couldn't load rom");
 
+    let key_timeout = settings.key_timeout.map(Duration::from_millis);
+    let mut pressed_at: [Option<Instant>; 16] = [None; 16];
+
+    let mut stats_since = Instant::now();
+    let mut stats_frames = chip.frame_count();
+    let mut stats_instructions = chip.instruction_count();
+
+    let mut keymap = settings.keymap;
+    let mut rebind = Rebind::Off;
+
     let mut pause = false;
+    let mut step_frame = false;
     loop {
+        let frame_start = Instant::now();
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => return,
+                // While rebinding, keys go to the rebind prompts
+                Event::KeyDown {
+                    keycode: Some(code),
+                    ..
+                } if rebind != Rebind::Off => {
+                    rebind = match (rebind, code) {
+                        (_, Keycode::Escape) => Rebind::Off,
+                        (Rebind::Choose, _) => keymap.chip_key(code).map_or(rebind, Rebind::Bind),
+                        // keep waiting for a key that is free to bind
+                        (Rebind::Bind(_), _) if is_reserved(code) => rebind,
+                        (Rebind::Bind(k), _) => {
+                            keymap.bind(k, code);
+                            Rebind::Off
+                        }
+                        (Rebind::Off, _) => Rebind::Off,
+                    };
+                    canvas
+                        .window_mut()
+                        .set_title(&rebind.prompt())
+                        .expect("couldn't set the window title");
+                }
                 Event::KeyDown {
                     keycode: Some(code),
+                    keymod,
                     ..
                 } => match code {
                     Keycode::Escape => return,
+                    Keycode::F9 => {
+                        rebind = Rebind::Choose;
+                        for (k, pressed) in pressed_at.iter_mut().enumerate() {
+                            chip.key_up(k);
+                            *pressed = None;
+                        }
+                        canvas
+                            .window_mut()
+                            .set_title(&rebind.prompt())
+                            .expect("couldn't set the window title");
+                    }
+                    Keycode::F10 => {
+                        println!("Controls:");
+                        for (k, name) in keymap.describe() {
+                            println!("  {:X}: {}", k, name);
+                        }
+                    }
                     Keycode::P => pause = !pause,
-                    Keycode::Num1 => chip.key_down(0x1),
-                    Keycode::Num2 => chip.key_down(0x2),
-                    Keycode::Num3 => chip.key_down(0x3),
-                    Keycode::Num4 => chip.key_down(0xc),
-                    Keycode::Q => chip.key_down(0x4),
-                    Keycode::W => chip.key_down(0x5),
-                    Keycode::E => chip.key_down(0x6),
-                    Keycode::R => chip.key_down(0xd),
-                    Keycode::A => chip.key_down(0x7),
-                    Keycode::S => chip.key_down(0x8),
-                    Keycode::D => chip.key_down(0x9),
-                    Keycode::F => chip.key_down(0xe),
-                    Keycode::Z => chip.key_down(0xa),
-                    Keycode::X => chip.key_down(0x0),
-                    Keycode::C => chip.key_down(0xb),
-                    Keycode::V => chip.key_down(0xf),
-                    _ => {}
+                    Keycode::N if pause => step_frame = true,
+                    _ => {
+                        if let Some(slot) = slots::slot(code) {
+                            let file = slots::slot_path(Path::new(&path), slot);
+                            if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                                let loaded: Result<(), Box<dyn Error>> = fs::read(&file)
+                                    .map_err(Into::into)
+                                    .and_then(|state| chip.load_state(&state).map_err(Into::into));
+                                match loaded {
+                                    Ok(()) => println!("Loaded {}", file.display()),
+                                    Err(e) => eprintln!("couldn't load {}: {}", file.display(), e),
+                                }
+                            } else {
+                                match fs::write(&file, chip.save_state()) {
+                                    Ok(()) => println!("Saved {}", file.display()),
+                                    Err(e) => eprintln!("couldn't save {}: {}", file.display(), e),
+                                }
+                            }
+                        } else if let Some(k) = keymap.chip_key(code) {
+                            chip.key_down(k);
+                            pressed_at[k] = Some(Instant::now());
+                        } else if settings.log_keys {
+                            println!("Unbound key: {}", code.name());
+                        }
+                    }
                 },
 
                 Event::KeyUp {
                     keycode: Some(code),
                     ..
-                } => match code {
-                    Keycode::Num1 => chip.key_up(0x1),
-                    Keycode::Num2 => chip.key_up(0x2),
-                    Keycode::Num3 => chip.key_up(0x3),
-                    Keycode::Num4 => chip.key_up(0xc),
-                    Keycode::Q => chip.key_up(0x4),
-                    Keycode::W => chip.key_up(0x5),
-                    Keycode::E => chip.key_up(0x6),
-                    Keycode::R => chip.key_up(0xd),
-                    Keycode::A => chip.key_up(0x7),
-                    Keycode::S => chip.key_up(0x8),
-                    Keycode::D => chip.key_up(0x9),
-                    Keycode::F => chip.key_up(0xe),
-                    Keycode::Z => chip.key_up(0xa),
-                    Keycode::X => chip.key_up(0x0),
-                    Keycode::C => chip.key_up(0xb),
-                    Keycode::V => chip.key_up(0xf),
-                    _ => {}
-                },
+                } => {
+                    if let Some(k) = keymap.chip_key(code) {
+                        chip.key_up(k);
+                        pressed_at[k] = None;
+                    }
+                }
+                // SDL won't send the key up events after losing focus
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
+                    ..
+                } => {
+                    for (k, pressed) in pressed_at.iter_mut().enumerate() {
+                        chip.key_up(k);
+                        *pressed = None;
+                    }
+                }
                 Event::DropFile { filename, .. } => {
                     let rom = get_rom(&filename);
                     chip.reset();
                     chip.load_rom(&rom).expect("couldn't load rom");
+                    path = filename;
                 }
 
                 _ => {}
             }
         }
 
-        // Go to the next frame if the game is not paused
-        if !pause {
+        // Release the keys that look stuck
+        if let Some(timeout) = key_timeout {
+            for (k, pressed) in pressed_at.iter_mut().enumerate() {
+                if pressed.is_some_and(|t| t.elapsed() > timeout) {
+                    chip.key_up(k);
+                    *pressed = None;
+                }
+            }
+        }
+
+        // Go to the next frame if the game is not paused or rebinding,
+        // or if a single frame was requested while paused
+        if (!pause && rebind == Rebind::Off) || step_frame {
             chip.frame(ipf).expect("emulation error");
+            step_frame = false;
         }
 
-        // Audio update
-        if chip.buzzer() {
-            sound.resume();
-        } else {
-            sound.pause();
+        // Audio update, muted while paused or rebinding
+        let buzzer = chip.buzzer() && !pause && rebind == Rebind::Off;
+        if let Some(sound) = &mut sound {
+            if recording.is_some() {
+                sound.lock().muted = !buzzer;
+            } else if buzzer {
+                sound.resume();
+            } else {
+                sound.pause();
+            }
         }
 
-        // Video update
-        let fb = chip.fb();
-        for (y, row) in fb.iter().enumerate() {
+        // Video update, with smaller pixels in the SUPER-CHIP high resolution
+        let (width, height) = chip.dimensions();
+        let pixel_size = scale * Chip8::screen_width() / width;
+        for (y, row) in chip.fb().enumerate() {
             for (x, pixel) in row.iter().enumerate() {
                 if *pixel {
-                    canvas.set_draw_color(Color::WHITE);
+                    canvas.set_draw_color(settings.fg);
                 } else {
-                    canvas.set_draw_color(Color::BLACK);
+                    canvas.set_draw_color(settings.bg);
                 }
                 canvas
                     .fill_rect(Rect::new(
-                        (x * SQUARE_SIZE) as i32,
-                        (y * SQUARE_SIZE) as i32,
-                        SQUARE_SIZE as u32,
-                        SQUARE_SIZE as u32,
+                        (x * pixel_size) as i32,
+                        (y * pixel_size) as i32,
+                        pixel_size as u32,
+                        pixel_size as u32,
                     ))
                     .expect("failed to draw a rect");
             }
         }
+        if settings.crt {
+            let scanline_size = pixel_size / 4;
+            canvas.set_draw_color(SCANLINE_COLOR);
+            for y in 0..height {
+                canvas
+                    .fill_rect(Rect::new(
+                        0,
+                        ((y + 1) * pixel_size - scanline_size) as i32,
+                        (width * pixel_size) as u32,
+                        scanline_size as u32,
+                    ))
+                    .expect("failed to draw a scanline");
+            }
+        }
         canvas.present();
 
-        // Wait for 15ms
-        std::thread::sleep(Duration::from_millis(15));
+        // Performance stats
+        if settings.stats && stats_since.elapsed() >= Duration::from_secs(1) {
+            let frames = chip.frame_count().saturating_sub(stats_frames);
+            let instructions = chip.instruction_count().saturating_sub(stats_instructions);
+            let secs = stats_since.elapsed().as_secs_f64();
+            println!(
+                "{:.1} fps, {:.0} ips",
+                frames as f64 / secs,
+                instructions as f64 / secs
+            );
+            stats_since = Instant::now();
+            stats_frames = chip.frame_count();
+            stats_instructions = chip.instruction_count();
+        }
+
+        // Wait for the rest of the frame
+        if let Some(rest) = chip.frame_period().checked_sub(frame_start.elapsed()) {
+            std::thread::sleep(rest);
+        }
     }
 }
@@ -0,0 +1,73 @@
+use std::sync::{Arc, Mutex};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+/// The samples played by the audio device, shared with its callback.
+pub type Samples = Arc<Mutex<Vec<f32>>>;
+
+/// Collects the buzzer samples, and writes them to a WAV file when dropped,
+/// so the recording is saved however the emulator quits.
+pub struct AudioRecording {
+    path: String,
+    rate: u32,
+    samples: Samples,
+}
+
+impl AudioRecording {
+    /// Records the samples pushed to `samples`, played at `rate` Hz.
+    pub fn new(path: String, rate: u32, samples: Samples) -> Self {
+        AudioRecording {
+            path,
+            rate,
+            samples,
+        }
+    }
+
+    /// Writes the samples recorded so far as a 16 bit mono WAV file.
+    pub fn save(&self) -> Result<(), hound::Error> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: self.rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&self.path, spec)?;
+        for &sample in self.samples.lock().expect("audio thread panicked").iter() {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+        writer.finalize()
+    }
+}
+
+impl Drop for AudioRecording {
+    fn drop(&mut self) {
+        match self.save() {
+            Ok(()) => println!("Audio saved to {}", self.path),
+            Err(e) => eprintln!("couldn't save the audio to {}: {}", self.path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_wav() {
+        let path = std::env::temp_dir().join("ironchip_recording_test.wav");
+        let samples = Samples::default();
+        samples.lock().unwrap().extend([0.0, 0.5, -1.0]);
+        // saved when dropped
+        drop(AudioRecording::new(
+            path.to_string_lossy().into_owned(),
+            44100,
+            samples,
+        ));
+
+        let mut reader = hound::WavReader::open(&path).expect("couldn't open the recording");
+        assert_eq!(reader.spec().sample_rate, 44100);
+        let read: Vec<i16> = reader.samples().map(|s| s.unwrap()).collect();
+        assert_eq!(read, [0, i16::MAX / 2, -i16::MAX]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
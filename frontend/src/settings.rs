@@ -0,0 +1,137 @@
+use clap::{ArgEnum, Parser};
+use sdl2::pixels::Color;
+
+use crate::keymap::KeyMap;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    /// Rom to open
+    #[clap(short, long)]
+    rom: Option<String>,
+
+    /// Instructions per frame
+    #[clap(long, default_value_t = 10)]
+    ipf: usize,
+
+    /// Release a key if no key event was received for it in this many milliseconds
+    #[clap(long)]
+    key_timeout: Option<u64>,
+
+    /// Darken the bottom of each pixel row, like the scanlines of a CRT
+    #[clap(long)]
+    crt: bool,
+
+    /// Print the frames and instructions per second every second
+    #[clap(long)]
+    stats: bool,
+
+    /// Run the timers at 50Hz, like PAL machines
+    #[clap(long)]
+    pal: bool,
+
+    /// Size in screen pixels of each Chip-8 pixel
+    #[clap(long, default_value_t = 16)]
+    scale: usize,
+
+    /// Volume of the buzzer, from 0 to 1
+    #[clap(long, default_value_t = 0.1)]
+    volume: f32,
+
+    /// Shape of the buzzer tone
+    #[clap(long, arg_enum, default_value_t = Waveform::Square)]
+    waveform: Waveform,
+
+    /// Record the buzzer to this WAV file, saved on exit
+    #[clap(long)]
+    record_audio: Option<String>,
+
+    /// Print the keyboard keys that are not bound to a Chip-8 key
+    #[clap(long)]
+    log_keys: bool,
+}
+
+/// The shape of the buzzer tone.
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+}
+
+impl Waveform {
+    /// Returns the sample at `phase`, from 0 to 1, of a wave going from -1 to 1.
+    pub fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase <= 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+        }
+    }
+}
+
+/// Everything the emulation loop can be configured with.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub rom: Option<String>,
+    pub ipf: usize,
+    /// Release a key if it got no key event for this many milliseconds.
+    pub key_timeout: Option<u64>,
+    pub crt: bool,
+    pub stats: bool,
+    pub pal: bool,
+    /// Color of the lit pixels.
+    pub fg: Color,
+    /// Color of the pixels turned off.
+    pub bg: Color,
+    pub keymap: KeyMap,
+    pub waveform: Waveform,
+    pub volume: f32,
+    /// Size in screen pixels of each Chip-8 pixel.
+    pub scale: usize,
+    /// WAV file to record the buzzer to.
+    pub record_audio: Option<String>,
+    /// Print the name of the unbound keys when pressed.
+    pub log_keys: bool,
+}
+
+impl From<Args> for Settings {
+    fn from(args: Args) -> Self {
+        Settings {
+            rom: args.rom,
+            ipf: args.ipf,
+            key_timeout: args.key_timeout,
+            crt: args.crt,
+            stats: args.stats,
+            pal: args.pal,
+            fg: Color::WHITE,
+            bg: Color::BLACK,
+            keymap: KeyMap::default(),
+            waveform: args.waveform,
+            volume: args.volume,
+            scale: args.scale,
+            record_audio: args.record_audio,
+            log_keys: args.log_keys,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_args() {
+        let args = Args::parse_from(["ironchip", "--ipf", "20", "--waveform", "triangle"]);
+        let settings = Settings::from(args);
+        assert_eq!(settings.ipf, 20);
+        assert_eq!(settings.waveform, Waveform::Triangle);
+        assert_eq!(settings.scale, 16);
+        assert_eq!(settings.rom, None);
+        assert_eq!(settings.keymap, KeyMap::default());
+    }
+}
@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+
+use sdl2::keyboard::Keycode;
+
+/// The keys of the save state slots, `F1` for slot 1 and so on.
+const SLOT_KEYS: [Keycode; 8] = [
+    Keycode::F1,
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F8,
+];
+
+/// Returns the save state slot of a keyboard key, from 1 to 8.
+pub fn slot(code: Keycode) -> Option<usize> {
+    SLOT_KEYS.iter().position(|&c| c == code).map(|n| n + 1)
+}
+
+/// Returns the file of a save state slot, next to the rom,
+/// like `games/pong.1.state` for slot 1 of `games/pong.ch8`.
+pub fn slot_path(rom: &Path, slot: usize) -> PathBuf {
+    let name = rom.file_stem().unwrap_or_default().to_string_lossy();
+    rom.with_file_name(format!("{}.{}.state", name, slot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_files() {
+        assert_eq!(slot(Keycode::F1), Some(1));
+        assert_eq!(slot(Keycode::F8), Some(8));
+        assert_eq!(slot(Keycode::F9), None);
+        assert_eq!(
+            slot_path(Path::new("games/pong.ch8"), 3),
+            Path::new("games/pong.3.state")
+        );
+        assert_eq!(
+            slot_path(Path::new("tetris"), 1),
+            Path::new("tetris.1.state")
+        );
+    }
+}